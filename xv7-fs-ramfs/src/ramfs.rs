@@ -12,6 +12,15 @@ use usyscall::error::*;
 use usyscall::fs::*;
 use Option::*;
 
+/* data of ramfs lives in memory pages */
+const RAMFS_BLOCKSIZE: usize = 4096;
+/* a name has to fit into `Direntory::name` with the trailing zero */
+const RAMFS_NAMELEN: usize = 255;
+/* mode of the root directory without a `mode` option, as in linux ramfs */
+const RAMFS_DEFAULT_MODE: u32 = 0o755;
+/* largest size of a file, see `s_maxbytes` in linux */
+const RAMFS_MAXBYTES: usize = 1 << 32;
+
 #[derive(new)]
 pub struct RamFS {
     #[new(default)]
@@ -19,28 +28,123 @@ pub struct RamFS {
     #[new(default)]
     max_inode: Mutex<usize>,
     #[new(default)]
+    this: Weak<RamFSLocked>,
+    #[new(default)]
     root: Weak<RamFSINodeLocked>,
     #[new(default)]
+    flags: MountFlag,
+    /* limits set by the `size`, `nr_blocks` and `nr_inodes` options, 0 means no limit */
+    #[new(default)]
+    max_blocks: usize,
+    #[new(default)]
+    max_inodes: usize,
+    #[new(default)]
     inodes: BTreeMap<usize, Arc<RamFSINodeLocked>>, /* inode cache */
     #[new(default)]
     data: BTreeMap<usize, NodeData>, /* persistent data */
 }
 
 impl RamFS {
-    pub fn mount(_: &str) -> (FSRef, DentryRef) {
-        let fs_inner = Arc::new(RamFSLocked(RwLock::new(RamFS::new())));
-        let root_inner = fs_inner
-            .alloc_inode(
-                &fs_inner,
-                Some(INodeMetaData {
-                    mode: INodeType::IFDIR,
-                    ..Default::default()
-                }),
-            )
-            .unwrap();
+    pub fn mount(_: &str, opts: &MountOptions) -> Result<(FSRef, DentryRef)> {
+        let ramfs_opts = RamFSOptions::parse(opts)?;
+        let fs_inner = Arc::new(RamFSLocked(RwLock::new(RamFS {
+            blocksize: RAMFS_BLOCKSIZE,
+            flags: opts.flags,
+            max_blocks: ramfs_opts.max_blocks.unwrap_or(0),
+            max_inodes: ramfs_opts.max_inodes.unwrap_or(0),
+            ..RamFS::new()
+        })));
+        fs_inner.0.write().this = Arc::downgrade(&fs_inner);
+        let root_inner = fs_inner.new_inode(Some(INodeMetaData {
+            mode: INodeType::IFDIR,
+            perm: ramfs_opts
+                .mode
+                .unwrap_or(INodePerm::from_bits_truncate(RAMFS_DEFAULT_MODE)),
+            uid: ramfs_opts.uid.unwrap_or(0),
+            gid: ramfs_opts.gid.unwrap_or(0),
+            /* `.` and `..` of the root both refer to itself */
+            nlink: 2,
+            ..Default::default()
+        }))?;
         fs_inner.0.write().root = Arc::downgrade(&root_inner);
         let dentry = root_inner.create_dentry(&root_inner, None, "/");
-        return (fs_inner, dentry);
+        Ok((fs_inner, dentry))
+    }
+
+    fn used_blocks(&self) -> usize {
+        self.data
+            .values()
+            .map(|data| data.data.len().div_ceil(self.blocksize))
+            .sum()
+    }
+
+    /* the length the data of `ino` may grow to within the block limit */
+    fn space_for(&self, ino: usize) -> Result<usize> {
+        let len = self
+            .data
+            .get(&ino)
+            .ok_or_else(|| Error::new(ENOENT))?
+            .data
+            .len();
+        if self.max_blocks == 0 {
+            return Ok(usize::MAX);
+        }
+        let own = len.div_ceil(self.blocksize);
+        let free = self.max_blocks.saturating_sub(self.used_blocks());
+        Ok((own + free) * self.blocksize)
+    }
+
+    /* a write which does not fit is cut short, EFBIG or ENOSPC if nothing fits */
+    fn write_data(&mut self, ino: usize, buf: &[u8], offset: usize) -> Result<usize> {
+        if !buf.is_empty() && offset >= RAMFS_MAXBYTES {
+            return Err(Error::new(EFBIG));
+        }
+        let limit = core::cmp::min(self.space_for(ino)?, RAMFS_MAXBYTES);
+        if !buf.is_empty() && offset >= limit {
+            return Err(Error::new(ENOSPC));
+        }
+        let len = core::cmp::min(buf.len(), limit.saturating_sub(offset));
+        let node_data = self.data.get_mut(&ino).unwrap();
+        node_data.write_at(&buf[..len], offset)
+    }
+}
+
+/* options of a ramfs mount, see `tmpfs` in linux */
+#[derive(Default)]
+struct RamFSOptions {
+    max_blocks: Option<usize>,
+    max_inodes: Option<usize>,
+    /* mode and owner of the root directory */
+    mode: Option<INodePerm>,
+    uid: Option<usize>,
+    gid: Option<usize>,
+}
+
+impl RamFSOptions {
+    fn parse(opts: &MountOptions) -> Result<RamFSOptions> {
+        let mut ramfs_opts = RamFSOptions::default();
+        for (key, value) in &opts.options {
+            /* every ramfs option takes a value */
+            let value = value.as_deref().ok_or_else(|| Error::new(EINVAL))?;
+            let number = |value: &str| value.parse::<usize>().map_err(|_| Error::new(EINVAL));
+            match key.as_str() {
+                "size" => {
+                    let size = memparse(value)?;
+                    ramfs_opts.max_blocks = Some(size.div_ceil(RAMFS_BLOCKSIZE));
+                }
+                "nr_blocks" => ramfs_opts.max_blocks = Some(memparse(value)?),
+                "nr_inodes" => ramfs_opts.max_inodes = Some(memparse(value)?),
+                "mode" => {
+                    let mode = u32::from_str_radix(value, 8).map_err(|_| Error::new(EINVAL))?;
+                    ramfs_opts.mode =
+                        Some(INodePerm::from_bits(mode).ok_or_else(|| Error::new(EINVAL))?);
+                }
+                "uid" => ramfs_opts.uid = Some(number(value)?),
+                "gid" => ramfs_opts.gid = Some(number(value)?),
+                _ => return Err(Error::new(EINVAL)),
+            }
+        }
+        Ok(ramfs_opts)
     }
 }
 
@@ -56,25 +160,58 @@ struct NodeData {
     metadata: INodeMetaData,
 }
 
+impl NodeData {
+    /* keep `size` and `blocks` consistent with the length of `data` */
+    fn update_size(&mut self) {
+        self.metadata.size = self.data.len();
+        self.metadata.blocks = self.data.len().div_ceil(512);
+    }
+    /* a write past the end fills the gap with zeros, an empty write changes nothing */
+    fn write_at(&mut self, buf: &[u8], offset: usize) -> Result<usize> {
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let end = offset
+            .checked_add(len)
+            .filter(|end| *end <= RAMFS_MAXBYTES)
+            .ok_or_else(|| Error::new(EFBIG))?;
+        if end > self.data.len() {
+            self.data.resize(end, 0);
+        }
+        self.data[offset..end].clone_from_slice(buf);
+        self.update_size();
+        Ok(len)
+    }
+    fn read_at(&self, buf: &mut [u8], offset: usize) -> usize {
+        if offset >= self.data.len() {
+            return 0;
+        }
+        let len = core::cmp::min(buf.len(), self.data.len() - offset);
+        buf[0..len].clone_from_slice(&self.data[offset..(offset + len)]);
+        len
+    }
+}
+
 pub struct RamFSLocked(RwLock<RamFS>);
 
 impl RamFSLocked {
-    fn alloc_inode(
-        &self,
-        fs_ref: &Arc<Self>,
-        metadata: Option<INodeMetaData>,
-    ) -> Result<Arc<RamFSINodeLocked>> {
+    fn new_inode(&self, metadata: Option<INodeMetaData>) -> Result<Arc<RamFSINodeLocked>> {
         let ino = {
             let ramfs = self.0.read();
+            if ramfs.max_inodes != 0 && ramfs.data.len() >= ramfs.max_inodes {
+                return Err(Error::new(ENOSPC));
+            }
             let mut locked = ramfs.max_inode.lock();
             *locked += 1;
             *locked
         };
         let inode = Arc::new(RamFSINodeLocked(RwLock::new(RamFSINode::new(
             ino,
-            Arc::downgrade(&fs_ref),
+            self.0.read().this.clone(),
         ))));
         let mut fsw = self.0.write();
+        let blksize = fsw.blocksize;
         fsw.inodes.insert(ino, inode.clone());
         fsw.data.insert(
             ino,
@@ -82,6 +219,7 @@ impl RamFSLocked {
                 metadata: {
                     let mut md = metadata.unwrap_or(Default::default());
                     md.ino = ino;
+                    md.blksize = blksize;
                     md
                 },
                 ..Default::default()
@@ -90,17 +228,122 @@ impl RamFSLocked {
         Ok(inode)
     }
 
-    fn link_inode(&self, parent: &INodeRef, sub: &INodeRef, name: &str) {
-        let sub_ino = sub.get_ino();
-        let parent_ino = parent.get_ino();
+    fn link_inode(&self, parent_ino: usize, sub_ino: usize, name: &str) {
         let mut fs = self.0.write();
+        let sub_data = fs.data.get_mut(&sub_ino).unwrap();
+        sub_data.metadata.nlink += 1;
+        let is_dir = sub_data.metadata.mode == INodeType::IFDIR;
+        if is_dir {
+            /* `.` of the new directory */
+            sub_data.metadata.nlink += 1;
+        }
         let parent_data = fs.data.get_mut(&parent_ino).unwrap();
         parent_data.children_ino.insert(String::from(name), sub_ino);
-        parent_data.metadata.nlink += 1;
+        if is_dir {
+            /* `..` of the new directory */
+            parent_data.metadata.nlink += 1;
+        }
+    }
+
+    fn unlink_inode(&self, parent_ino: usize, name: &str) -> Result<usize> {
+        let mut fs = self.0.write();
+        let parent_data = fs
+            .data
+            .get_mut(&parent_ino)
+            .ok_or_else(|| Error::new(ENOENT))?;
+        let sub_ino = parent_data
+            .children_ino
+            .remove(name)
+            .ok_or_else(|| Error::new(ENOENT))?;
         let sub_data = fs.data.get_mut(&sub_ino).unwrap();
-        sub_data.metadata.nlink += 1;
+        sub_data.metadata.nlink -= 1;
+        if sub_data.metadata.mode == INodeType::IFDIR {
+            sub_data.metadata.nlink -= 1;
+            fs.data.get_mut(&parent_ino).unwrap().metadata.nlink -= 1;
+        }
+        Ok(sub_ino)
     }
-    fn get_inode(&self, fs_ref: &Arc<Self>, ino: usize) -> Result<Arc<RamFSINodeLocked>> {
+    fn rename_inode(
+        &self,
+        parent_ino: usize,
+        name: &str,
+        new_parent_ino: usize,
+        new_name: &str,
+        flags: RenameFlag,
+    ) -> Result<()> {
+        let mut fs = self.0.write();
+        let sub_ino = *fs
+            .data
+            .get(&parent_ino)
+            .and_then(|data| data.children_ino.get(name))
+            .ok_or_else(|| Error::new(ENOENT))?;
+        let target_ino = fs
+            .data
+            .get(&new_parent_ino)
+            .ok_or_else(|| Error::new(ENOENT))?
+            .children_ino
+            .get(new_name)
+            .copied();
+        let is_dir = |fs: &RamFS, ino: usize| fs.data[&ino].metadata.mode == INodeType::IFDIR;
+        match target_ino {
+            Some(target_ino) if flags.contains(RenameFlag::RENAME_EXCHANGE) => {
+                fs.data
+                    .get_mut(&parent_ino)
+                    .unwrap()
+                    .children_ino
+                    .insert(String::from(name), target_ino);
+                fs.data
+                    .get_mut(&new_parent_ino)
+                    .unwrap()
+                    .children_ino
+                    .insert(String::from(new_name), sub_ino);
+                /* `..` of exchanged directories now refer to the other parent */
+                if is_dir(&fs, target_ino) {
+                    fs.data.get_mut(&new_parent_ino).unwrap().metadata.nlink -= 1;
+                    fs.data.get_mut(&parent_ino).unwrap().metadata.nlink += 1;
+                }
+                if is_dir(&fs, sub_ino) {
+                    fs.data.get_mut(&parent_ino).unwrap().metadata.nlink -= 1;
+                    fs.data.get_mut(&new_parent_ino).unwrap().metadata.nlink += 1;
+                }
+                return Ok(());
+            }
+            None if flags.contains(RenameFlag::RENAME_EXCHANGE) => {
+                return Err(Error::new(ENOENT));
+            }
+            Some(_) if flags.contains(RenameFlag::RENAME_NOREPLACE) => {
+                return Err(Error::new(EEXIST));
+            }
+            Some(target_ino) => {
+                /* the replaced target loses its name */
+                let target_is_dir = is_dir(&fs, target_ino);
+                let target_data = fs.data.get_mut(&target_ino).unwrap();
+                target_data.metadata.nlink -= 1;
+                if target_is_dir {
+                    target_data.metadata.nlink -= 1;
+                    fs.data.get_mut(&new_parent_ino).unwrap().metadata.nlink -= 1;
+                }
+            }
+            None => {}
+        }
+        fs.data
+            .get_mut(&parent_ino)
+            .unwrap()
+            .children_ino
+            .remove(name);
+        fs.data
+            .get_mut(&new_parent_ino)
+            .unwrap()
+            .children_ino
+            .insert(String::from(new_name), sub_ino);
+        if is_dir(&fs, sub_ino) {
+            fs.data.get_mut(&parent_ino).unwrap().metadata.nlink -= 1;
+            fs.data.get_mut(&new_parent_ino).unwrap().metadata.nlink += 1;
+        }
+        Ok(())
+    }
+
+    fn iget(&self, ino: usize) -> Result<Arc<RamFSINodeLocked>> {
         let mut fs = self.0.write();
         if let Some(inode) = fs.inodes.get(&ino) {
             return Ok(inode.clone());
@@ -109,7 +352,7 @@ impl RamFSLocked {
             return {
                 let inode = Arc::new(RamFSINodeLocked(RwLock::new(RamFSINode::new(
                     ino,
-                    Arc::downgrade(&fs_ref),
+                    fs.this.clone(),
                 ))));
                 fs.inodes.insert(ino, inode.clone());
                 Ok(inode)
@@ -119,8 +362,80 @@ impl RamFSLocked {
     }
 }
 impl FileSystem for RamFSLocked {
-    fn todo(&self) {
-        todo!()
+    fn get_root(&self) -> Result<INodeRef> {
+        let root = self.0.read().root.upgrade();
+        let root = root.ok_or_else(|| Error::new(ENOENT))?;
+        Ok(root)
+    }
+    fn get_inode(&self, ino: usize) -> Result<INodeRef> {
+        let inode = self.iget(ino)?;
+        Ok(inode)
+    }
+    fn get_flags(&self) -> MountFlag {
+        self.0.read().flags
+    }
+    fn alloc_inode(&self, metadata: INodeMetaData) -> Result<INodeRef> {
+        let inode = self.new_inode(Some(metadata))?;
+        Ok(inode)
+    }
+    fn evict_inode(&self, inode: &INodeRef) -> Result<()> {
+        let ino = inode.get_ino();
+        let mut fs = self.0.write();
+        /* the data of a file with links must be kept */
+        match fs.data.get(&ino) {
+            Some(data) if data.metadata.nlink == 0 => {}
+            Some(_) => return Err(Error::new(EBUSY)),
+            None => return Err(Error::new(ENOENT)),
+        }
+        fs.data.remove(&ino);
+        fs.inodes.remove(&ino);
+        Ok(())
+    }
+    fn put_super(&self) -> Result<()> {
+        let mut fs = self.0.write();
+        fs.inodes.clear();
+        fs.data.clear();
+        Ok(())
+    }
+    fn sync_fs(&self, _wait: bool) -> Result<()> {
+        /* nothing to write out from memory */
+        Ok(())
+    }
+    fn statfs(&self, buf: &mut StatFs) -> Result<()> {
+        let fs = self.0.read();
+        /* without a limit only the used blocks and inodes are counted */
+        let used_blocks = fs.used_blocks();
+        buf.bsize = fs.blocksize;
+        buf.blocks = used_blocks;
+        buf.files = fs.data.len();
+        if fs.max_blocks != 0 {
+            buf.blocks = fs.max_blocks;
+            buf.bfree = fs.max_blocks.saturating_sub(used_blocks);
+            buf.bavail = buf.bfree;
+        }
+        if fs.max_inodes != 0 {
+            buf.files = fs.max_inodes;
+            buf.ffree = fs.max_inodes.saturating_sub(fs.data.len());
+        }
+        buf.namelen = RAMFS_NAMELEN;
+        Ok(())
+    }
+    fn remount(&self, opts: &MountOptions) -> Result<()> {
+        /* the mode and owner of the root are only applied by mount */
+        let ramfs_opts = RamFSOptions::parse(opts)?;
+        let mut fs = self.0.write();
+        /* a limit can not be set below what is already used */
+        let max_blocks = ramfs_opts.max_blocks.unwrap_or(fs.max_blocks);
+        let max_inodes = ramfs_opts.max_inodes.unwrap_or(fs.max_inodes);
+        if (max_blocks != 0 && max_blocks < fs.used_blocks())
+            || (max_inodes != 0 && max_inodes < fs.data.len())
+        {
+            return Err(Error::new(EINVAL));
+        }
+        fs.max_blocks = max_blocks;
+        fs.max_inodes = max_inodes;
+        fs.flags = opts.flags;
+        Ok(())
     }
 }
 
@@ -136,12 +451,13 @@ pub struct RamFSINode {
 pub struct RamFSINodeLocked(RwLock<RamFSINode>);
 
 impl RamFSINodeLocked {
-    fn get_node_data(&self) -> NodeData {
+    /* the data of an evicted inode is gone, even if the inode is still referenced */
+    fn get_node_data(&self) -> Result<NodeData> {
         let fs = self.get_fs_special();
         let fs = fs.0.read();
         let ino = self.0.read().ino;
-        let data = fs.data.get(&ino).unwrap();
-        data.clone()
+        let data = fs.data.get(&ino).ok_or_else(|| Error::new(ENOENT))?;
+        Ok(data.clone())
     }
 
     fn get_fs_special(&self) -> Arc<RamFSLocked> {
@@ -177,43 +493,53 @@ impl RamFSINodeLocked {
         return dentry;
     }
 
-    fn create_entity(&self, dentry: &DentryRef, name: &str, mode: INodeType) -> Result<DentryRef> {
+    fn create_entity(
+        &self,
+        dentry: &DentryRef,
+        name: &str,
+        mut metadata: INodeMetaData,
+        attr: &INodeAttr,
+    ) -> Result<DentryRef> {
+        apply_init_attr(&mut metadata, attr);
         let fs = self.get_fs_special();
-        let inode = fs
-            .alloc_inode(
-                &fs,
-                Some(INodeMetaData {
-                    mode: mode,
-                    ..Default::default()
-                }),
-            )
-            .unwrap();
-        fs.link_inode(
-            &dentry.read().inode.upgrade().unwrap(),
-            &{ inode.clone() },
-            name,
-        );
+        let inode = fs.new_inode(Some(metadata))?;
+        fs.link_inode(dentry.read().get_inode()?.get_ino(), inode.get_ino(), name);
         let dentry = inode.create_dentry(&inode, Some(dentry.clone()), name);
         Ok(dentry)
     }
 }
+fn apply_init_attr(metadata: &mut INodeMetaData, attr: &INodeAttr) {
+    if attr.valid.contains(AttrMask::ATTR_MODE) {
+        metadata.perm = attr.perm;
+    }
+    if attr.valid.contains(AttrMask::ATTR_UID) {
+        metadata.uid = attr.uid;
+    }
+    if attr.valid.contains(AttrMask::ATTR_GID) {
+        metadata.gid = attr.gid;
+    }
+}
 impl INode for RamFSINodeLocked {
     fn get_ino(&self) -> usize {
         return self.0.read().ino;
     }
 
-    fn get_metadata(&self) -> INodeMetaData {
+    fn get_metadata(&self) -> Result<INodeMetaData> {
         let fs = self.get_fs_special();
         let fs = fs.0.read();
-        let data = fs.data.get(&self.get_ino()).unwrap();
-        data.metadata.clone()
+        let data = fs
+            .data
+            .get(&self.get_ino())
+            .ok_or_else(|| Error::new(ENOENT))?;
+        Ok(data.metadata.clone())
     }
 
     fn set_metadata(&self, metadata: &INodeMetaData) {
         let fs = self.get_fs_special();
         let mut fs = fs.0.write();
-        let data = fs.data.get_mut(&self.get_ino()).unwrap();
-        data.metadata = metadata.clone();
+        if let Some(data) = fs.data.get_mut(&self.get_ino()) {
+            data.metadata = metadata.clone();
+        }
     }
     fn get_fs(&self) -> FSRef {
         return self.0.read().fs.upgrade().unwrap();
@@ -221,13 +547,21 @@ impl INode for RamFSINodeLocked {
     fn get_dentries(&self) -> Vec<DentryRef> {
         return self.0.read().dentries.clone();
     }
+    fn get_fop(&self) -> FileOperationsRef {
+        let fs = self.get_fs_special();
+        let ino = self.get_ino();
+        match self.get_metadata().map(|md| md.mode) {
+            Ok(INodeType::IFDIR) => Arc::new(RamFSDirOps::new(fs, ino)),
+            _ => Arc::new(RamFSFileOps::new(fs, ino)),
+        }
+    }
 
     fn lookup(&self, dir: &DentryRef, name: &str) -> Result<DentryRef> {
-        let node_data = self.get_node_data();
+        let node_data = self.get_node_data()?;
         match node_data.children_ino.get(name) {
             Some(ino) => {
                 let fs = self.get_fs_special();
-                let inode = fs.get_inode(&fs, *ino)?;
+                let inode = fs.iget(*ino)?;
                 let dentry = inode.create_dentry(&inode, Some(dir.clone()), name);
                 return Ok(dentry);
             }
@@ -235,27 +569,51 @@ impl INode for RamFSINodeLocked {
         }
     }
 
-    fn mkdir(&self, dentry: &DentryRef, name: &str) -> Result<DentryRef> {
-        self.create_entity(dentry, name, INodeType::IFDIR)
+    fn mkdir(&self, dentry: &DentryRef, name: &str, attr: &INodeAttr) -> Result<DentryRef> {
+        self.create_entity(dentry, name, INodeMetaData::new(INodeType::IFDIR), attr)
     }
 
     fn unlink(&self, dentry: &DentryRef, name: &str) -> Result<()> {
         let fs = self.get_fs_special();
-        let mut fsw = fs.0.write();
-        let node_data = fsw
-            .data
-            .get_mut(&self.get_ino())
-            .ok_or_else(|| Error::new(ENOENT))?;
-        node_data
-            .children_ino
-            .remove(name)
-            .ok_or_else(|| Error::new(ENOENT))?;
+        fs.unlink_inode(self.get_ino(), name)?;
         dentry.write().subdirs.remove(name);
         Ok(())
     }
 
-    fn create(&self, dentry: &DentryRef, name: &str) -> Result<DentryRef> {
-        self.create_entity(dentry, name, INodeType::IFREG)
+    fn link(&self, dentry: &DentryRef, name: &str, target: &DentryRef) -> Result<DentryRef> {
+        let fs = self.get_fs_special();
+        let ino = target.read().get_inode()?.get_ino();
+        let inode = fs.iget(ino)?;
+        fs.link_inode(self.get_ino(), ino, name);
+        Ok(inode.create_dentry(&inode, Some(dentry.clone()), name))
+    }
+
+    fn create(&self, dentry: &DentryRef, name: &str, attr: &INodeAttr) -> Result<DentryRef> {
+        self.create_entity(dentry, name, INodeMetaData::new(INodeType::IFREG), attr)
+    }
+
+    fn symlink(
+        &self,
+        dentry: &DentryRef,
+        name: &str,
+        target: &str,
+        attr: &INodeAttr,
+    ) -> Result<DentryRef> {
+        self.create_entity(
+            dentry,
+            name,
+            INodeMetaData {
+                mode: INodeType::IFLNK,
+                link: String::from(target),
+                size: target.len(),
+                ..Default::default()
+            },
+            attr,
+        )
+    }
+
+    fn readlink(&self, _dentry: &DentryRef) -> Result<String> {
+        Ok(self.get_metadata()?.link)
     }
 
     fn readdir_inodes(&self, _dentry: &DentryRef) -> Result<BTreeMap<String, usize>> {
@@ -268,65 +626,184 @@ impl INode for RamFSINodeLocked {
         Ok(node_data.children_ino.clone())
     }
 
-    fn write(&self, file: &FileRef, buf: &[u8]) -> Result<usize> {
+    fn getattr(&self, _dentry: &DentryRef, stat: &mut Stat) -> Result<()> {
+        let md = self.get_metadata()?;
+        stat.mode = md.mode;
+        stat.perm = md.perm.bits() as usize;
+        stat.uid = md.uid;
+        stat.gid = md.gid;
+        stat.ino = md.ino;
+        stat.atime = md.atime;
+        stat.mtime = md.mtime;
+        stat.ctime = md.ctime;
+        stat.nlink = md.nlink;
+        stat.rdev = md.rdev;
+        stat.size = md.size;
+        stat.blksize = md.blksize;
+        stat.blocks = md.blocks;
+        Ok(())
+    }
+
+    fn update_time(&self, time: usize, flags: TimeFlag) -> Result<()> {
+        let fs = self.get_fs_special();
+        let mut fsw = fs.0.write();
+        let md = &mut fsw
+            .data
+            .get_mut(&self.get_ino())
+            .ok_or_else(|| Error::new(ENOENT))?
+            .metadata;
+        if flags.contains(TimeFlag::S_ATIME) {
+            md.atime = time;
+        }
+        if flags.contains(TimeFlag::S_MTIME) {
+            md.mtime = time;
+        }
+        if flags.contains(TimeFlag::S_CTIME) {
+            md.ctime = time;
+        }
+        Ok(())
+    }
+
+    fn setattr(&self, _dentry: &DentryRef, attr: &INodeAttr) -> Result<()> {
         let fs = self.get_fs_special();
         let mut fsw = fs.0.write();
+        if attr.valid.contains(AttrMask::ATTR_SIZE) && attr.size > RAMFS_MAXBYTES {
+            return Err(Error::new(EFBIG));
+        }
+        if attr.valid.contains(AttrMask::ATTR_SIZE) && attr.size > fsw.space_for(self.get_ino())? {
+            return Err(Error::new(ENOSPC));
+        }
         let node_data = fsw
             .data
             .get_mut(&self.get_ino())
             .ok_or_else(|| Error::new(ENOENT))?;
+        let md = &mut node_data.metadata;
+        if attr.valid.contains(AttrMask::ATTR_MODE) {
+            md.perm = attr.perm;
+        }
+        if attr.valid.contains(AttrMask::ATTR_UID) {
+            md.uid = attr.uid;
+        }
+        if attr.valid.contains(AttrMask::ATTR_GID) {
+            md.gid = attr.gid;
+        }
+        if attr.valid.contains(AttrMask::ATTR_ATIME) {
+            md.atime = attr.atime;
+        }
+        if attr.valid.contains(AttrMask::ATTR_MTIME) {
+            md.mtime = attr.mtime;
+        }
+        if attr.valid.contains(AttrMask::ATTR_CTIME) {
+            md.ctime = attr.ctime;
+        }
+        if attr.valid.contains(AttrMask::ATTR_SIZE) {
+            /* the extended part is filled with zero */
+            node_data.data.resize(attr.size, 0);
+            node_data.update_size();
+        }
+        Ok(())
+    }
+    fn rename(
+        &self,
+        _dentry: &DentryRef,
+        name: &str,
+        new_dentry: &DentryRef,
+        new_name: &str,
+        flags: RenameFlag,
+    ) -> Result<()> {
+        let fs = self.get_fs_special();
+        let new_dir_ino = new_dentry.read().get_inode()?.get_ino();
+        fs.rename_inode(self.get_ino(), name, new_dir_ino, new_name, flags)
+    }
+    fn tmpfile(&self, _dentry: &DentryRef, attr: &INodeAttr) -> Result<DentryRef> {
+        let mut metadata = INodeMetaData::new(INodeType::IFREG);
+        apply_init_attr(&mut metadata, attr);
+        let fs = self.get_fs_special();
+        let inode = fs.new_inode(Some(metadata))?;
+        /* not linked into the directory, so the dentry has neither parent nor name */
+        Ok(inode.create_dentry(&inode, None, ""))
+    }
+}
+
+/* file operations of an open regular file */
+#[derive(new)]
+pub struct RamFSFileOps {
+    fs: Arc<RamFSLocked>,
+    ino: usize,
+}
+
+impl FileOperations for RamFSFileOps {
+    fn write(&self, file: &FileRef, buf: &[u8]) -> Result<usize> {
+        /* the open file is locked before the filesystem, as in `vfs_writev` */
         let mut fw = file.write();
+        let mut fsw = self.fs.0.write();
+        let data_len = fsw
+            .data
+            .get(&self.ino)
+            .ok_or_else(|| Error::new(ENOENT))?
+            .data
+            .len();
         if fw.mode.contains(FileMode::O_APPEND) {
-            fw.pos = node_data.data.len();
+            fw.pos = data_len;
         }
-        let len = buf.len();
-        if fw.pos + len > node_data.data.len() {
-            node_data.data.resize(fw.pos + len, 0);
-        }
-        node_data.data[fw.pos..(fw.pos + len)].clone_from_slice(&buf[0..len]);
+        let len = fsw.write_data(self.ino, buf, fw.pos)?;
         fw.pos += len;
         Ok(len)
     }
 
+    fn write_at(&self, _file: &FileRef, buf: &[u8], offset: usize) -> Result<usize> {
+        self.fs.0.write().write_data(self.ino, buf, offset)
+    }
+
     fn read(&self, file: &FileRef, buf: &mut [u8]) -> Result<usize> {
-        let fs = self.get_fs_special();
-        let mut fsr = fs.0.write();
-        let node_data = fsr
-            .data
-            .get_mut(&self.get_ino())
-            .ok_or_else(|| Error::new(ENOENT))?;
         let mut fw = file.write();
-        if fw.pos >= node_data.data.len() {
-            return Ok(1usize);
-        }
-        let len = core::cmp::min(buf.len(), node_data.data.len() - fw.pos);
-        buf[0..len].clone_from_slice(&node_data.data[fw.pos..(fw.pos + len)]);
+        let fsr = self.fs.0.read();
+        let node_data = fsr.data.get(&self.ino).ok_or_else(|| Error::new(ENOENT))?;
+        let len = node_data.read_at(buf, fw.pos);
         fw.pos += len;
         Ok(len)
     }
 
+    fn read_at(&self, _file: &FileRef, buf: &mut [u8], offset: usize) -> Result<usize> {
+        let fsr = self.fs.0.read();
+        let node_data = fsr.data.get(&self.ino).ok_or_else(|| Error::new(ENOENT))?;
+        Ok(node_data.read_at(buf, offset))
+    }
+
+    fn fsync(&self, _file: &FileRef, _datasync: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+/* file operations of an open directory */
+#[derive(new)]
+pub struct RamFSDirOps {
+    fs: Arc<RamFSLocked>,
+    ino: usize,
+}
+
+impl FileOperations for RamFSDirOps {
     fn readdir(&self, file: &FileRef, dirs: &mut [Direntory]) -> Result<usize> {
-        let fs = self.get_fs_special();
-        let fsr = fs.0.write();
-        let node_data = fsr
-            .data
-            .get(&self.get_ino())
-            .ok_or_else(|| Error::new(ENOENT))?;
-        if file.read().pos >= node_data.children_ino.len() {
+        /* the open file is locked before the filesystem, see `RamFSFileOps::write` */
+        let mut fw = file.write();
+        let fsr = self.fs.0.read();
+        let node_data = fsr.data.get(&self.ino).ok_or_else(|| Error::new(ENOENT))?;
+        let index = fw.pos.saturating_sub(DIR_DOT_ENTRIES);
+        if index >= node_data.children_ino.len() {
             return Ok(0usize);
         }
-        let mut entity = node_data.children_ino.iter().skip(file.read().pos);
+        let mut entity = node_data.children_ino.iter().skip(index);
         let mut count = 0;
-        for i in 0..dirs.len() {
+        for dir in dirs.iter_mut() {
             match entity.next() {
                 None => break,
                 Some(next) => {
-                    dirs[i].ino = *next.1;
-                    dirs[i].off = file.read().pos;
-                    dirs[i].name_len = next.0.len();
-                    dirs[i].name[0..next.0.len()].clone_from_slice(next.0.as_bytes());
-                    dirs[i].name[next.0.len()] = 0;
-                    file.write().pos += 1;
+                    dir.ino = *next.1;
+                    dir.off = fw.pos;
+                    dir.name_len = next.0.len();
+                    dir.name[0..next.0.len()].clone_from_slice(next.0.as_bytes());
+                    dir.name[next.0.len()] = 0;
+                    fw.pos += 1;
                     count += 1;
                 }
             };
@@ -334,33 +811,7 @@ impl INode for RamFSINodeLocked {
         Ok(count)
     }
 
-    fn getattr(&self, _dentry: &DentryRef, stat: &mut Stat) -> Result<()> {
-        let md = self.get_metadata();
-        stat.mode = md.mode;
-        stat.uid = md.uid;
-        stat.gid = md.gid;
-        stat.ino = md.ino;
-        stat.atime = md.atime;
-        stat.mtime = md.mtime;
-        stat.ctime = md.ctime;
-        stat.nlink = md.nlink;
+    fn fsync(&self, _file: &FileRef, _datasync: bool) -> Result<()> {
         Ok(())
     }
-    // fn rename(
-    //     &self,
-    //     dentry: &DentryRef,
-    //     name: &str,
-    //     new_name: &str,
-    //     flag: usize,
-    // ) -> Result<()> {
-    //     let fs = self.get_fs_special();
-    //     let mut fsw = fs.0.write();
-    //     let node_data = fsw.data.get_mut(&self.0.read().ino).ok_or_else(|| Error::new(ENOENT))?;
-    //     let sub_entity_ino =  node_data.children_ino.remove(name).ok_or_else(|| Error::new(ENOENT))?;
-    //     node_data.children_ino.insert(new_name.to_string(), sub_entity_ino);
-    //     if let Some(sub_entity_dentry) = dentry.write().subdirs.remove(name) {
-    //         dentry.write().subdirs.insert(new_name.to_string(), sub_entity_dentry);
-    //     };
-    //     Ok(())
-    // }
 }
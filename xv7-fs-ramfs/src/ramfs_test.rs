@@ -1,13 +1,88 @@
 #[cfg(test)]
 mod tests {
     use crate::ramfs;
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use lazy_static::lazy_static;
-    use spin::Mutex;
+    use spin::{Mutex, RwLock};
     use usyscall::error::*;
     use usyscall::fs::*;
+    use xv7_fs::fdtable::*;
     use xv7_fs::vfs::*;
     lazy_static! {
         pub static ref REGISTERED_FS: Mutex<RegisteredFS> = Mutex::new(RegisteredFS::new());
+        pub static ref CONTEXT: Mutex<Option<FSContextRef>> = Mutex::new(None);
+        pub static ref CLOCK: Arc<FakeClock> = Arc::new(FakeClock(AtomicUsize::new(0)));
+        pub static ref CREDENTIALS: Mutex<Credentials> = Mutex::new(Credentials::root());
+    }
+
+    pub struct FakeClock(AtomicUsize);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    /* a pseudo file counting its reads in the private data of each open file */
+    #[derive(Default)]
+    pub struct CounterOps {
+        flushed: AtomicUsize,
+        released: AtomicUsize,
+        /* make every flush fail with EIO */
+        fail_flush: AtomicBool,
+        /* make every release fail with EIO */
+        fail_release: AtomicBool,
+    }
+
+    impl FileOperations for CounterOps {
+        fn read(&self, file: &FileRef, buf: &mut [u8]) -> Result<usize> {
+            let mut fw = file.write();
+            let count = fw
+                .private_data
+                .as_mut()
+                .and_then(|data| data.downcast_mut::<usize>())
+                .ok_or_else(|| Error::new(EBADF))?;
+            *count += 1;
+            buf[0] = *count as u8;
+            Ok(1)
+        }
+        fn open(&self, _inode: &INodeRef, file: &FileRef) -> Result<()> {
+            file.write().private_data = Some(Box::new(0usize));
+            Ok(())
+        }
+        fn flush(&self, _file: &FileRef) -> Result<()> {
+            self.flushed.fetch_add(1, Ordering::SeqCst);
+            if self.fail_flush.load(Ordering::SeqCst) {
+                return Err(Error::new(EIO));
+            }
+            Ok(())
+        }
+        fn release(&self, _inode: &INodeRef, _file: &FileRef) -> Result<()> {
+            self.released.fetch_add(1, Ordering::SeqCst);
+            if self.fail_release.load(Ordering::SeqCst) {
+                return Err(Error::new(EIO));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_time(time: usize) {
+        CLOCK.0.store(time, Ordering::SeqCst);
+    }
+
+    fn current_context() -> FSContextRef {
+        CONTEXT.lock().as_ref().unwrap().clone()
+    }
+
+    fn current_cred() -> Credentials {
+        CREDENTIALS.lock().clone()
+    }
+
+    fn set_cred(cred: Credentials) {
+        *CREDENTIALS.lock() = cred;
     }
 
     #[allow(unused_must_use)]
@@ -16,9 +91,14 @@ mod tests {
         REGISTERED_FS
             .lock()
             .register_fs(FSType::RAMFS, ramfs::RamFS::mount);
-        let (_rootfs, root_dentry) = REGISTERED_FS.lock().mount_fs(FSType::RAMFS, "".into());
+        REGISTERED_FS.lock().set_clock(CLOCK.clone());
+        let (_rootfs, root_dentry) = REGISTERED_FS
+            .lock()
+            .mount_fs(FSType::RAMFS, "", &MountOptions::default())
+            .unwrap();
 
         REGISTERED_FS.lock().set_root(&root_dentry);
+        *CONTEXT.lock() = Some(REGISTERED_FS.lock().new_context());
         println!("[REGISTERED_FS]: {}", *REGISTERED_FS.lock());
         println!("[root]: {}", *REGISTERED_FS.lock().get_root().read());
 
@@ -65,7 +145,6 @@ mod tests {
         assert_eq!(test_vfs_unlink("/abc/test_dir3"), Err(Error::new(ENOENT)));
         let file = test_vfs_open("/test_file_2", FileMode::O_RDWR);
         assert!(file.is_ok());
-        assert_eq!(test_vfs_unlink("/test_file_2"), Err(Error::new(EBUSY)));
         assert_eq!(test_vfs_close(&mut file.unwrap()), Ok(()));
         assert_eq!(test_vfs_unlink("/test_file_2"), Ok(()));
 
@@ -111,89 +190,2632 @@ mod tests {
         assert_eq!(test_vfs_mkdir("/test_vfs_readdir"), Ok(()));
         assert_eq!(test_vfs_mkdir("/test_vfs_readdir/test_dir"), Ok(()));
         assert_eq!(test_vfs_mkdir("/test_vfs_readdir/test_dir2"), Ok(()));
-        let file = test_vfs_open("/test_vfs_readdir", FileMode::O_RDWR);
+        let file = test_vfs_open("/test_vfs_readdir", FileMode::O_RDONLY);
         assert!(file.is_ok());
         let file = file.unwrap();
-        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(2));
-        assert_eq!(&dirs[0].name[0..dirs[0].name_len], "test_dir".as_bytes());
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(3));
+        assert_eq!(&dirs[0].name[0..dirs[0].name_len], ".".as_bytes());
+        assert_eq!(test_vfs_stat("/test_vfs_readdir", &mut stat), Ok(()));
+        assert_eq!(dirs[0].ino, stat.ino);
+        assert_eq!(&dirs[1].name[0..dirs[1].name_len], "..".as_bytes());
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!(dirs[1].ino, stat.ino);
+        assert_eq!(&dirs[2].name[0..dirs[2].name_len], "test_dir".as_bytes());
         assert_eq!(
             test_vfs_stat("/test_vfs_readdir/test_dir", &mut stat),
             Ok(())
         );
-        assert_eq!(dirs[0].ino, stat.ino);
-        assert_eq!(&dirs[1].name[0..dirs[1].name_len], "test_dir2".as_bytes());
+        assert_eq!(dirs[2].ino, stat.ino);
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(1));
+        assert_eq!(&dirs[0].name[0..dirs[0].name_len], "test_dir2".as_bytes());
         assert_eq!(
             test_vfs_stat("/test_vfs_readdir/test_dir2", &mut stat),
             Ok(())
         );
-        assert_eq!(dirs[1].ino, stat.ino);
+        assert_eq!(dirs[0].ino, stat.ino);
         assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(0));
         assert_eq!(test_vfs_close(&file), Ok(()));
-    }
 
-    fn test_vfs_lookup(path: &str) -> Result<()> {
-        println!(
-            "[vfs_lookup ({})]: {}",
-            path,
-            *REGISTERED_FS.lock().vfs_lookup(path)?.read()
+        // test for vfs_chdir
+        assert_eq!(test_vfs_lookup(""), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_chdir("/test_file_rw"), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_chdir("/not_exist"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_chdir("/abc"), Ok(()));
+        assert_eq!(test_vfs_lookup("test_dir"), Ok(()));
+        assert_eq!(test_vfs_lookup("test_file_rw"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_mkdir("rel_dir"), Ok(()));
+        assert_eq!(test_vfs_lookup("/abc/rel_dir"), Ok(()));
+        assert_eq!(test_vfs_create("rel_dir/rel_file"), Ok(()));
+        assert_eq!(test_vfs_stat("rel_dir/rel_file", &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFREG);
+        let file = test_vfs_open("rel_dir/rel_file", FileMode::O_RDWR);
+        assert!(file.is_ok());
+        assert_eq!(test_vfs_close(&file.unwrap()), Ok(()));
+        assert_eq!(test_vfs_chdir("rel_dir"), Ok(()));
+        assert_eq!(test_vfs_lookup("rel_file"), Ok(()));
+        assert_eq!(test_vfs_unlink("rel_file"), Ok(()));
+        assert_eq!(
+            test_vfs_lookup("/abc/rel_dir/rel_file"),
+            Err(Error::new(ENOENT))
         );
-        Ok(())
-    }
 
-    fn test_vfs_mkdir(path: &str) -> Result<()> {
-        println!(
-            "[vfs_mkdir ({})]: {}",
-            path,
-            *REGISTERED_FS.lock().vfs_mkdir(path)?.read()
+        // test for vfs_fchdir
+        let file = test_vfs_open("/abc/test_dir", FileMode::O_RDONLY);
+        assert!(file.is_ok());
+        let file = file.unwrap();
+        assert_eq!(test_vfs_fchdir(&file), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_create("fchdir_file"), Ok(()));
+        assert_eq!(test_vfs_lookup("/abc/test_dir/fchdir_file"), Ok(()));
+        let file = test_vfs_open("/test_file_rw", FileMode::O_RDONLY);
+        assert!(file.is_ok());
+        let file = file.unwrap();
+        assert_eq!(test_vfs_fchdir(&file), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
+        assert_eq!(test_vfs_lookup("abc/test_dir/fchdir_file"), Ok(()));
+
+        // test for `.` and `..`
+        let mut stat2 = Stat::default();
+        let mut stat3 = Stat::default();
+        assert_eq!(test_vfs_lookup("/abc/../test_file"), Ok(()));
+        assert_eq!(test_vfs_lookup("/abc/./test_dir/.."), Ok(()));
+        assert_eq!(test_vfs_lookup("/abc/test_dir/../../abc/test_dir2"), Ok(()));
+        assert_eq!(
+            test_vfs_lookup("/abc/not_exist/.."),
+            Err(Error::new(ENOENT))
         );
-        Ok(())
-    }
-    fn test_vfs_unlink(path: &str) -> Result<()> {
-        REGISTERED_FS.lock().vfs_unlink(path)?;
-        println!("[vfs_unlink ({})]", path,);
-        Ok(())
-    }
+        assert_eq!(test_vfs_lookup("/test_file/.."), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_stat("/../../..", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/", &mut stat2), Ok(()));
+        assert_eq!(stat.ino, stat2.ino);
+        assert_eq!(test_vfs_chdir("/abc/test_dir"), Ok(()));
+        assert_eq!(test_vfs_stat(".", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/abc/test_dir", &mut stat2), Ok(()));
+        assert_eq!(stat.ino, stat2.ino);
+        assert_eq!(test_vfs_lookup("../test_dir2"), Ok(()));
+        assert_eq!(test_vfs_lookup("../../test_file"), Ok(()));
+        assert_eq!(test_vfs_chdir(".."), Ok(()));
+        assert_eq!(test_vfs_lookup("test_dir"), Ok(()));
+        assert_eq!(test_vfs_mkdir("test_dir/.."), Err(Error::new(EEXIST)));
+        assert_eq!(test_vfs_create("test_dir/."), Err(Error::new(EEXIST)));
+        assert_eq!(test_vfs_unlink("test_dir/."), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_unlink("test_dir/.."), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
 
-    fn test_vfs_create(path: &str) -> Result<()> {
-        println!(
-            "[vfs_create ({})]: {}",
-            path,
-            *REGISTERED_FS.lock().vfs_create(path)?.read()
+        // `..` can not escape from the root of a task
+        let ctx = REGISTERED_FS.lock().fork_context(&current_context());
+        {
+            let root = REGISTERED_FS
+                .lock()
+                .vfs_lookup(&ctx, &current_cred(), "/abc")
+                .unwrap();
+            let mut ctxw = ctx.write();
+            ctxw.root = root.clone();
+            ctxw.cwd = root;
+        }
+        assert!(REGISTERED_FS
+            .lock()
+            .vfs_lookup(&ctx, &current_cred(), "../test_dir")
+            .is_ok());
+        assert!(REGISTERED_FS
+            .lock()
+            .vfs_lookup(&ctx, &current_cred(), "/../../test_dir")
+            .is_ok());
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_lookup(&ctx, &current_cred(), "/../test_file")
+                .map(|_| ()),
+            Err(Error::new(ENOENT))
         );
-        Ok(())
-    }
-    fn test_vfs_open(path: &str, mode: FileMode) -> Result<FileRef> {
-        let file = REGISTERED_FS.lock().vfs_open(path, mode)?;
-        println!("[vfs_open ({})]: {}", path, *file.read());
-        Ok(file)
-    }
 
-    fn test_vfs_close(file: &FileRef) -> Result<()> {
-        REGISTERED_FS.lock().vfs_close(file)?;
-        println!("[vfs_close ({})]", *file.read());
-        Ok(())
-    }
+        // test for vfs_symlink
+        let mut link_buf = [0u8; 32];
+        assert_eq!(test_vfs_symlink("/abc", "/link_abc"), Ok(()));
+        assert_eq!(
+            test_vfs_symlink("/abc", "/link_abc"),
+            Err(Error::new(EEXIST))
+        );
+        assert_eq!(test_vfs_symlink("", "/link_empty"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_lookup("/link_abc/test_dir"), Ok(()));
+        assert_eq!(test_vfs_stat("/link_abc", &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFDIR);
+        assert_eq!(test_vfs_lstat("/link_abc", &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFLNK);
+        assert_eq!(test_vfs_symlink("test_dir", "/abc/rel_link"), Ok(()));
+        assert_eq!(test_vfs_stat("/abc/rel_link", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/abc/test_dir", &mut stat2), Ok(()));
+        assert_eq!(stat.ino, stat2.ino);
+        assert_eq!(test_vfs_lookup("/link_abc/rel_link/../test_dir2"), Ok(()));
+        assert_eq!(test_vfs_chdir("/link_abc/rel_link/"), Ok(()));
+        assert_eq!(test_vfs_lookup("fchdir_file"), Ok(()));
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
 
-    fn test_vfs_write(file: &FileRef, data: &[u8]) -> Result<usize> {
-        let ret = REGISTERED_FS.lock().vfs_write(file, data)?;
-        println!("[vfs_write ({} {:?})] ret: {}", *file.read(), data, ret);
-        Ok(ret)
-    }
+        // `/bin -> /usr/bin`
+        assert_eq!(test_vfs_mkdir("/usr"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/usr/bin"), Ok(()));
+        assert_eq!(test_vfs_create("/usr/bin/sh"), Ok(()));
+        assert_eq!(test_vfs_symlink("/usr/bin", "/bin"), Ok(()));
+        let file = test_vfs_open("/bin/sh", FileMode::O_RDWR);
+        assert!(file.is_ok());
+        assert_eq!(test_vfs_close(&file.unwrap()), Ok(()));
+        assert_eq!(test_vfs_create("/bin/ls"), Ok(()));
+        assert_eq!(test_vfs_lookup("/usr/bin/ls"), Ok(()));
 
-    fn test_vfs_read(file: &FileRef, data: &mut [u8]) -> Result<usize> {
-        let ret = REGISTERED_FS.lock().vfs_read(file, data)?;
-        println!("[vfs_read ({} {:?})] ret: {}", *file.read(), data, ret);
-        Ok(ret)
-    }
+        // test for vfs_readlink
+        assert_eq!(test_vfs_readlink("/bin", &mut link_buf), Ok(8));
+        assert_eq!(&link_buf[0..8], "/usr/bin".as_bytes());
+        assert_eq!(test_vfs_readlink("/bin", &mut link_buf[0..4]), Ok(4));
+        assert_eq!(&link_buf[0..4], "/usr".as_bytes());
+        assert_eq!(test_vfs_readlink("/abc/rel_link", &mut link_buf), Ok(8));
+        assert_eq!(&link_buf[0..8], "test_dir".as_bytes());
+        assert_eq!(
+            test_vfs_readlink("/usr/bin", &mut link_buf),
+            Err(Error::new(EINVAL))
+        );
 
-    fn test_vfs_readdir(file: &FileRef, dirs: &mut [Direntory]) -> Result<usize> {
-        let ret = REGISTERED_FS.lock().vfs_readdir(file, dirs)?;
-        println!("[vfs_readdir ({} {:?})] ret: {}", *file.read(), dirs, ret);
-        Ok(ret)
-    }
-    fn test_vfs_stat(path: &str, stat: &mut Stat) -> Result<()> {
-        REGISTERED_FS.lock().vfs_stat(path, stat)?;
-        println!("[vfs_stat ({} {:?})]", path, stat);
+        // dangling and looping links
+        assert_eq!(test_vfs_symlink("/not_exist", "/dangling"), Ok(()));
+        assert_eq!(
+            test_vfs_stat("/dangling", &mut stat),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_lstat("/dangling", &mut stat), Ok(()));
+        assert_eq!(test_vfs_mkdir("/dangling"), Err(Error::new(EEXIST)));
+        assert_eq!(test_vfs_unlink("/dangling"), Ok(()));
+        assert_eq!(
+            test_vfs_lstat("/dangling", &mut stat),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_symlink("/loop_b", "/loop_a"), Ok(()));
+        assert_eq!(test_vfs_symlink("loop_a", "/loop_b"), Ok(()));
+        assert_eq!(test_vfs_lookup("/loop_a"), Err(Error::new(ELOOP)));
+        assert_eq!(test_vfs_lookup("/loop_b/abc"), Err(Error::new(ELOOP)));
+        assert_eq!(test_vfs_lstat("/loop_a", &mut stat), Ok(()));
+        assert_eq!(test_vfs_symlink("/test_file", "/link_file"), Ok(()));
+        assert_eq!(test_vfs_lookup("/link_file/abc"), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_lookup("/link_file/"), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_lstat("/link_file", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 1);
+
+        // test for vfs_link
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        let root_nlink = stat.nlink;
+        assert_eq!(test_vfs_mkdir("/nlink_dir"), Ok(()));
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, root_nlink + 1);
+        assert_eq!(test_vfs_stat("/nlink_dir", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 2);
+        assert_eq!(test_vfs_mkdir("/nlink_dir/sub"), Ok(()));
+        assert_eq!(test_vfs_stat("/nlink_dir", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 3);
+        assert_eq!(test_vfs_unlink("/nlink_dir/sub"), Ok(()));
+        assert_eq!(test_vfs_stat("/nlink_dir", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 2);
+        assert_eq!(test_vfs_create("/nlink_dir/file"), Ok(()));
+        assert_eq!(test_vfs_stat("/nlink_dir", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 2);
+        assert_eq!(test_vfs_stat("/nlink_dir/file", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 1);
+        assert_eq!(test_vfs_link("/nlink_dir/file", "/nlink_dir/file2"), Ok(()));
+        assert_eq!(test_vfs_link("/nlink_dir/file", "/file3"), Ok(()));
+        assert_eq!(test_vfs_stat("/nlink_dir/file2", &mut stat2), Ok(()));
+        assert_eq!(stat2.nlink, 3);
+        assert_eq!(stat.ino, stat2.ino);
+        assert_eq!(
+            test_vfs_link("/nlink_dir/file", "/nlink_dir/file2"),
+            Err(Error::new(EEXIST))
+        );
+        assert_eq!(
+            test_vfs_link("/nlink_dir/not_exist", "/nlink_dir/file4"),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(
+            test_vfs_link("/nlink_dir", "/nlink_dir2"),
+            Err(Error::new(EPERM))
+        );
+        let file = test_vfs_open("/nlink_dir/file2", FileMode::O_WRONLY).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_unlink("/nlink_dir/file"), Ok(()));
+        assert_eq!(test_vfs_stat("/file3", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 2);
+        let file = test_vfs_open("/file3", FileMode::O_RDONLY).unwrap();
+        assert_eq!(
+            test_vfs_read(&file, &mut buf[0..data1.len()]),
+            Ok(data1.len())
+        );
+        assert_eq!(buf[0..data1.len()], data1[..]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_unlink("/nlink_dir/file2"), Ok(()));
+        assert_eq!(test_vfs_unlink("/nlink_dir"), Ok(()));
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, root_nlink);
+        assert_eq!(test_vfs_stat("/file3", &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 1);
+
+        // test for vfs_rename
+        let noreplace = RenameFlag::RENAME_NOREPLACE;
+        let exchange = RenameFlag::RENAME_EXCHANGE;
+        assert_eq!(test_vfs_mkdir("/mv_a"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/mv_b"), Ok(()));
+        assert_eq!(test_vfs_create("/mv_a/f"), Ok(()));
+        let file = test_vfs_open("/mv_a/f", FileMode::O_WRONLY).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/mv_a/f", &mut stat), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_a/f", "/mv_b/g", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_lookup("/mv_a/f"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_stat("/mv_b/g", &mut stat2), Ok(()));
+        assert_eq!(stat.ino, stat2.ino);
+        let file = test_vfs_open("/mv_b/g", FileMode::O_RDONLY).unwrap();
+        assert_eq!(
+            test_vfs_read(&file, &mut buf[0..data1.len()]),
+            Ok(data1.len())
+        );
+        assert_eq!(buf[0..data1.len()], data1[..]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_b/g", "/mv_b/g", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_b/g", "/mv_b/h", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_b/g", "/mv_b/h", RenameFlag::empty()),
+            Err(Error::new(ENOENT))
+        );
+
+        // replace, RENAME_NOREPLACE and RENAME_EXCHANGE
+        assert_eq!(test_vfs_create("/mv_b/i"), Ok(()));
+        assert_eq!(test_vfs_stat("/mv_b/h", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/mv_b/i", &mut stat2), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_b/h", "/mv_b/i", noreplace),
+            Err(Error::new(EEXIST))
+        );
+        assert_eq!(test_vfs_rename("/mv_b/h", "/mv_b/i", exchange), Ok(()));
+        assert_eq!(test_vfs_stat("/mv_b/h", &mut stat3), Ok(()));
+        assert_eq!(stat3.ino, stat2.ino);
+        assert_eq!(test_vfs_stat("/mv_b/i", &mut stat3), Ok(()));
+        assert_eq!(stat3.ino, stat.ino);
+        assert_eq!(
+            test_vfs_rename("/mv_b/i", "/mv_b/j", exchange),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_b/i", "/mv_b/h", noreplace | exchange),
+            Err(Error::new(EINVAL))
+        );
+        let victim = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mv_b/h")
+            .unwrap();
+        assert_eq!(
+            test_vfs_rename("/mv_b/i", "/mv_b/h", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_lookup("/mv_b/i"), Err(Error::new(ENOENT)));
+        /* the replaced dentry is dropped from the cache */
+        assert!(victim.read().parent.upgrade().is_none());
+        let moved = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mv_b/h")
+            .unwrap();
+        assert!(!Arc::ptr_eq(&moved, &victim));
+        drop(victim);
+        assert_eq!(test_vfs_stat("/mv_b/h", &mut stat3), Ok(()));
+        assert_eq!(stat3.ino, stat.ino);
+        assert_eq!(stat3.nlink, 1);
+
+        // move directories
+        assert_eq!(test_vfs_mkdir("/mv_a/sub"), Ok(()));
+        assert_eq!(test_vfs_create("/mv_a/sub/file"), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_a", "/mv_a/sub/x", RenameFlag::empty()),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_a", "/mv_a/sub", exchange),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_a", "/mv_b/h", RenameFlag::empty()),
+            Err(Error::new(ENOTDIR))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_b/h", "/mv_a", RenameFlag::empty()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_b", "/mv_a", RenameFlag::empty()),
+            Err(Error::new(ENOTEMPTY))
+        );
+        assert_eq!(
+            test_vfs_rename("/", "/mv_a/root", RenameFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(
+            test_vfs_rename("/mv_a/.", "/mv_b/x", RenameFlag::empty()),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(test_vfs_stat("/mv_a", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/mv_b", &mut stat2), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_a/sub", "/mv_b/sub", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/mv_a", &mut stat3), Ok(()));
+        assert_eq!(stat3.nlink, stat.nlink - 1);
+        assert_eq!(test_vfs_stat("/mv_b/sub/..", &mut stat3), Ok(()));
+        assert_eq!(stat3.ino, stat2.ino);
+        assert_eq!(stat3.nlink, stat2.nlink + 1);
+        assert_eq!(test_vfs_lookup("/mv_b/sub/file"), Ok(()));
+        assert_eq!(test_vfs_lookup("/mv_a/sub/file"), Err(Error::new(ENOENT)));
+        let sub = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mv_b/sub")
+            .unwrap();
+        let mv_b = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mv_b")
+            .unwrap();
+        assert!(Arc::ptr_eq(&sub.read().parent.upgrade().unwrap(), &mv_b));
+        assert!(mv_b.read().subdirs.contains_key("sub"));
+        assert_eq!(
+            test_vfs_rename("/mv_a", "/mv_b/sub", RenameFlag::empty()),
+            Err(Error::new(ENOTEMPTY))
+        );
+        assert_eq!(test_vfs_unlink("/mv_b/sub/file"), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mv_a", "/mv_b/sub", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_lookup("/mv_a"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_stat("/mv_b", &mut stat3), Ok(()));
+        assert_eq!(stat3.nlink, stat2.nlink + 1);
+        assert_eq!(test_vfs_stat("/mv_b/sub", &mut stat3), Ok(()));
+        assert_eq!(stat3.ino, stat.ino);
+        assert_eq!(stat3.nlink, 2);
+
+        // test for vfs_chmod
+        let perm = INodePerm::S_IRUSR | INodePerm::S_IWUSR | INodePerm::S_IRGRP;
+        assert_eq!(test_vfs_create("/attr_file"), Ok(()));
+        assert_eq!(test_vfs_chmod("/attr_file", perm), Ok(()));
+        assert_eq!(test_vfs_chmod("/not_exist", perm), Err(Error::new(ENOENT)));
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/attr_file")
+            .unwrap();
+        let inode = dentry.read().get_inode().unwrap();
+        assert_eq!(inode.get_metadata().unwrap().perm, perm);
+        assert_eq!(test_vfs_symlink("/attr_file", "/attr_link"), Ok(()));
+        assert_eq!(test_vfs_chmod("/attr_link", INodePerm::S_ISVTX), Ok(()));
+        assert_eq!(inode.get_metadata().unwrap().perm, INodePerm::S_ISVTX);
+
+        // test for vfs_chown
+        assert_eq!(test_vfs_chown("/attr_file", Some(1000), Some(100)), Ok(()));
+        assert_eq!(test_vfs_stat("/attr_file", &mut stat), Ok(()));
+        assert_eq!((stat.uid, stat.gid), (1000, 100));
+        assert_eq!(test_vfs_chown("/attr_file", None, Some(200)), Ok(()));
+        assert_eq!(test_vfs_stat("/attr_file", &mut stat), Ok(()));
+        assert_eq!((stat.uid, stat.gid), (1000, 200));
+        assert_eq!(test_vfs_chown("/attr_file", Some(0), None), Ok(()));
+        assert_eq!(test_vfs_stat("/attr_file", &mut stat), Ok(()));
+        assert_eq!((stat.uid, stat.gid), (0, 200));
+
+        // test for vfs_utimensat
+        assert_eq!(
+            test_vfs_utimensat("/attr_file", UTime::At(10), UTime::At(20)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/attr_file", &mut stat), Ok(()));
+        assert_eq!((stat.atime, stat.mtime), (10, 20));
+        assert_eq!(
+            test_vfs_utimensat("/attr_file", UTime::Omit, UTime::At(30)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/attr_file", &mut stat), Ok(()));
+        assert_eq!((stat.atime, stat.mtime), (10, 30));
+
+        // test for vfs_truncate and vfs_ftruncate
+        let file = test_vfs_open("/attr_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &data2), Ok(data2.len()));
+        assert_eq!(test_vfs_truncate("/attr_file", 3), Ok(()));
+        assert_eq!(test_vfs_truncate("/abc", 3), Err(Error::new(EISDIR)));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/attr_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..data2.len()]), Ok(3));
+        assert_eq!(buf[0..3], data2[0..3]);
+        assert_eq!(test_vfs_ftruncate(&file, 8), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/attr_file", FileMode::O_WRONLY).unwrap();
+        assert_eq!(test_vfs_ftruncate(&file, 8), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/attr_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..10]), Ok(8));
+        assert_eq!(buf[0..3], data2[0..3]);
+        assert_eq!(buf[3..8], [0; 5]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/abc", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_ftruncate(&file, 0), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+
+        // test for timestamps
+        let times = |stat: &Stat| (stat.atime, stat.mtime, stat.ctime);
+        set_time(100);
+        assert_eq!(test_vfs_mkdir("/time_dir"), Ok(()));
+        assert_eq!(test_vfs_create("/time_dir/file"), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 100, 100));
+        set_time(200);
+        let file = test_vfs_open("/time_dir/file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 200, 200));
+        set_time(300);
+        assert_eq!(test_vfs_write(&file, &[]), Ok(0));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 200, 200));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/time_dir/file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..2]), Ok(2));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (300, 200, 200));
+        set_time(400);
+        assert_eq!(test_vfs_chmod("/time_dir/file", INodePerm::S_IRUSR), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (300, 200, 400));
+        set_time(500);
+        assert_eq!(test_vfs_truncate("/time_dir/file", 1), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (300, 500, 500));
+        set_time(600);
+        assert_eq!(
+            test_vfs_utimensat("/time_dir/file", UTime::Now, UTime::Omit),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/time_dir/file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (600, 500, 600));
+        assert_eq!(test_vfs_stat("/time_dir", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 100, 100));
+        set_time(700);
+        assert_eq!(test_vfs_create("/time_dir/file2"), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 700, 700));
+        set_time(800);
+        assert_eq!(
+            test_vfs_rename("/time_dir/file", "/time_file", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/time_dir", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 800, 800));
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!((stat.mtime, stat.ctime), (800, 800));
+        assert_eq!(test_vfs_stat("/time_file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (600, 500, 800));
+        set_time(900);
+        assert_eq!(test_vfs_link("/time_file", "/time_dir/file"), Ok(()));
+        assert_eq!(test_vfs_stat("/time_file", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (600, 500, 900));
+        set_time(1000);
+        assert_eq!(test_vfs_unlink("/time_dir/file2"), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (100, 1000, 1000));
+        let file = test_vfs_open("/time_dir", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(3));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/time_dir", &mut stat), Ok(()));
+        assert_eq!(times(&stat), (1000, 1000, 1000));
+
+        // test for size, blocks and permission bits
+        let big = vec![7u8; 1000];
+        assert_eq!(test_vfs_create("/size_file"), Ok(()));
+        assert_eq!(test_vfs_stat("/size_file", &mut stat), Ok(()));
+        assert_eq!((stat.size, stat.blocks, stat.blksize), (0, 0, 4096));
+        let file = test_vfs_open("/size_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &big), Ok(big.len()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.size, stat.blocks), (1000, 2));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_stat("/size_file", &mut stat2), Ok(()));
+        assert_eq!((stat2.size, stat2.blocks), (1005, 2));
+        assert_eq!(test_vfs_ftruncate(&file, 10), Ok(()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.size, stat.blocks), (10, 1));
+        assert_eq!(test_vfs_truncate("/size_file", 0), Ok(()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.size, stat.blocks), (0, 0));
+        assert_eq!(test_vfs_chmod("/size_file", perm), Ok(()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFREG);
+        assert_eq!(stat.perm, 0o640);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_symlink("/size_file", "/size_link"), Ok(()));
+        assert_eq!(test_vfs_lstat("/size_link", &mut stat), Ok(()));
+        assert_eq!(stat.size, "/size_file".len());
+        assert_eq!(test_vfs_chmod("/abc", INodePerm::S_ISVTX), Ok(()));
+        assert_eq!(test_vfs_stat("/abc", &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFDIR);
+        assert_eq!(stat.perm, 0o1000);
+
+        // test for permission checks
+        let mode = INodePerm::from_bits_truncate;
+        let user = Credentials::new(1000, 1000);
+        let other = Credentials {
+            groups: vec![100],
+            ..Credentials::new(2000, 2000)
+        };
+        assert_eq!(test_vfs_mkdir("/dac"), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac", mode(0o755)), Ok(()));
+        assert_eq!(test_vfs_mkdir("/dac/private"), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/private", mode(0o700)), Ok(()));
+        assert_eq!(test_vfs_create("/dac/private/file"), Ok(()));
+        assert_eq!(test_vfs_create("/dac/root_file"), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/root_file", mode(0o644)), Ok(()));
+        assert_eq!(test_vfs_create("/dac/user_file"), Ok(()));
+        assert_eq!(
+            test_vfs_chown("/dac/user_file", Some(1000), Some(1000)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_chmod("/dac/user_file", mode(0o600)), Ok(()));
+        assert_eq!(test_vfs_create("/dac/group_file"), Ok(()));
+        assert_eq!(test_vfs_chown("/dac/group_file", None, Some(100)), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/group_file", mode(0o060)), Ok(()));
+        assert_eq!(test_vfs_mkdir("/dac/sticky"), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/sticky", mode(0o1777)), Ok(()));
+        assert_eq!(test_vfs_create("/dac/sticky/user_file"), Ok(()));
+        assert_eq!(
+            test_vfs_chown("/dac/sticky/user_file", Some(1000), None),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/dac/sticky/other_file"), Ok(()));
+        assert_eq!(
+            test_vfs_chown("/dac/sticky/other_file", Some(2000), None),
+            Ok(())
+        );
+        /* root ignores the permission bits */
+        let file = test_vfs_open("/dac/group_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_lookup("/dac/private/file"), Ok(()));
+
+        set_cred(user.clone());
+        /* search permission on every directory of the path */
+        assert_eq!(
+            test_vfs_lookup("/dac/private/file"),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(test_vfs_lookup("/dac/private"), Ok(()));
+        assert_eq!(test_vfs_chdir("/dac/private"), Err(Error::new(EACCES)));
+        assert_eq!(test_vfs_stat("/dac/root_file", &mut stat), Ok(()));
+        /* write permission on the parent directory */
+        assert_eq!(test_vfs_create("/dac/new_file"), Err(Error::new(EACCES)));
+        assert_eq!(test_vfs_mkdir("/dac/new_dir"), Err(Error::new(EACCES)));
+        assert_eq!(
+            test_vfs_symlink("/dac", "/dac/new_link"),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            test_vfs_link("/dac/user_file", "/dac/new_link"),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(test_vfs_unlink("/dac/user_file"), Err(Error::new(EACCES)));
+        assert_eq!(
+            test_vfs_rename("/dac/user_file", "/dac/sticky/moved", RenameFlag::empty()),
+            Err(Error::new(EACCES))
+        );
+        /* permission bits of the file itself */
+        let file = test_vfs_open("/dac/root_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(
+            test_vfs_open("/dac/root_file", FileMode::O_WRONLY).map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        let file = test_vfs_open("/dac/user_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(
+            test_vfs_open("/dac/group_file", FileMode::O_RDONLY).map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            test_vfs_truncate("/dac/root_file", 0),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(test_vfs_truncate("/dac/user_file", 0), Ok(()));
+        /* only the owner changes the attributes */
+        assert_eq!(
+            test_vfs_chmod("/dac/root_file", mode(0o666)),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(test_vfs_chmod("/dac/user_file", mode(0o644)), Ok(()));
+        assert_eq!(
+            test_vfs_chown("/dac/user_file", Some(0), None),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_chown("/dac/user_file", None, Some(100)),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_chown("/dac/user_file", Some(1000), Some(1000)),
+            Ok(())
+        );
+        assert_eq!(
+            test_vfs_utimensat("/dac/root_file", UTime::Now, UTime::Now),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            test_vfs_utimensat("/dac/root_file", UTime::At(1), UTime::Omit),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_utimensat("/dac/user_file", UTime::At(1), UTime::At(2)),
+            Ok(())
+        );
+        /* in a sticky directory only the owner may remove a file */
+        assert_eq!(test_vfs_create("/dac/sticky/new_file"), Ok(()));
+        assert_eq!(
+            test_vfs_unlink("/dac/sticky/other_file"),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_rename(
+                "/dac/sticky/other_file",
+                "/dac/sticky/moved",
+                RenameFlag::empty()
+            ),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_rename(
+                "/dac/sticky/user_file",
+                "/dac/sticky/other_file",
+                RenameFlag::empty()
+            ),
+            Err(Error::new(EPERM))
+        );
+        assert_eq!(
+            test_vfs_rename(
+                "/dac/sticky/user_file",
+                "/dac/sticky/moved",
+                RenameFlag::empty()
+            ),
+            Ok(())
+        );
+
+        set_cred(other.clone());
+        /* supplementary groups are used for the group bits */
+        let file = test_vfs_open("/dac/group_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(
+            test_vfs_utimensat("/dac/group_file", UTime::Now, UTime::Omit),
+            Ok(())
+        );
+        assert_eq!(test_vfs_unlink("/dac/sticky/moved"), Err(Error::new(EPERM)));
+        /* giving a file to another group clears its set-id bits */
+        assert_eq!(
+            test_vfs_chmod("/dac/sticky/other_file", mode(0o6755)),
+            Ok(())
+        );
+        assert_eq!(
+            test_vfs_chown("/dac/sticky/other_file", None, Some(100)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sticky/other_file", &mut stat), Ok(()));
+        assert_eq!((stat.gid, stat.perm), (100, 0o755));
+        /* the set-group-id bit is dropped if the owner is not in the group */
+        set_cred(Credentials::root());
+        assert_eq!(
+            test_vfs_chown("/dac/sticky/other_file", None, Some(3000)),
+            Ok(())
+        );
+        set_cred(other.clone());
+        assert_eq!(
+            test_vfs_chmod("/dac/sticky/other_file", mode(0o2755)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sticky/other_file", &mut stat), Ok(()));
+        assert_eq!(stat.perm, 0o755);
+        assert_eq!(test_vfs_unlink("/dac/sticky/other_file"), Ok(()));
+        set_cred(Credentials::root());
+        assert_eq!(test_vfs_unlink("/dac/sticky/moved"), Ok(()));
+        /* exchanging directories across parents rewrites `..` of both of them */
+        assert_eq!(test_vfs_mkdir("/dac/xch_a"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/dac/xch_b"), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/xch_a", mode(0o777)), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/xch_b", mode(0o777)), Ok(()));
+        assert_eq!(
+            test_vfs_mkdir_mode("/dac/xch_b/root_dir", mode(0o755)),
+            Ok(())
+        );
+        set_cred(other.clone());
+        assert_eq!(test_vfs_mkdir("/dac/xch_a/user_dir"), Ok(()));
+        assert_eq!(
+            test_vfs_rename(
+                "/dac/xch_a/user_dir",
+                "/dac/xch_b/root_dir",
+                RenameFlag::RENAME_EXCHANGE
+            ),
+            Err(Error::new(EACCES))
+        );
+        set_cred(Credentials::root());
+        assert_eq!(test_vfs_chmod("/dac/xch_b/root_dir", mode(0o777)), Ok(()));
+        set_cred(other.clone());
+        assert_eq!(
+            test_vfs_rename(
+                "/dac/xch_a/user_dir",
+                "/dac/xch_b/root_dir",
+                RenameFlag::RENAME_EXCHANGE
+            ),
+            Ok(())
+        );
+        set_cred(Credentials::root());
+
+        // test for umask and the owner of new inodes
+        set_cred(user.clone());
+        assert_eq!(
+            test_vfs_create_mode("/dac/sticky/mode_file", mode(0o666)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sticky/mode_file", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid, stat.gid), (0o644, 1000, 1000));
+        assert_eq!(
+            test_vfs_mkdir_mode("/dac/sticky/mode_dir", mode(0o5777)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sticky/mode_dir", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid, stat.gid), (0o1755, 1000, 1000));
+        assert_eq!(
+            test_vfs_symlink("mode_file", "/dac/sticky/mode_link"),
+            Ok(())
+        );
+        assert_eq!(test_vfs_lstat("/dac/sticky/mode_link", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid), (0o777, 1000));
+        let old = REGISTERED_FS
+            .lock()
+            .vfs_umask(&current_context(), mode(0o077));
+        assert_eq!(old, mode(0o022));
+        assert_eq!(
+            test_vfs_create_mode("/dac/sticky/mode_file2", mode(0o666)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sticky/mode_file2", &mut stat), Ok(()));
+        assert_eq!(stat.perm, 0o600);
+        assert_eq!(test_vfs_mkdir("/dac/sticky/mode_dir2"), Ok(()));
+        assert_eq!(test_vfs_stat("/dac/sticky/mode_dir2", &mut stat), Ok(()));
+        assert_eq!(stat.perm, 0o700);
+        REGISTERED_FS.lock().vfs_umask(&current_context(), old);
+        /* a set-group-id directory passes its group on */
+        set_cred(Credentials::root());
+        assert_eq!(test_vfs_mkdir("/dac/sgid"), Ok(()));
+        assert_eq!(test_vfs_chown("/dac/sgid", None, Some(3000)), Ok(()));
+        assert_eq!(test_vfs_chmod("/dac/sgid", mode(0o2777)), Ok(()));
+        set_cred(user.clone());
+        assert_eq!(test_vfs_create("/dac/sgid/file"), Ok(()));
+        assert_eq!(test_vfs_stat("/dac/sgid/file", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid, stat.gid), (0o644, 1000, 3000));
+        assert_eq!(test_vfs_mkdir("/dac/sgid/dir"), Ok(()));
+        assert_eq!(test_vfs_stat("/dac/sgid/dir", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.gid), (0o2755, 3000));
+        /* but the creator can not make a file set-group-id to a foreign group */
+        assert_eq!(
+            test_vfs_create_mode("/dac/sgid/file2", mode(0o2775)),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/dac/sgid/file2", &mut stat), Ok(()));
+        assert_eq!(stat.perm, 0o755);
+        set_cred(Credentials::root());
+
+        // test for vfs_open with O_CREAT, O_EXCL and O_TRUNC
+        assert_eq!(
+            test_vfs_open("/open_file", FileMode::O_RDWR).map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        let file = test_vfs_open_mode(
+            "/open_file",
+            FileMode::O_RDWR | FileMode::O_CREAT,
+            mode(0o640),
+        )
+        .unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/open_file", &mut stat), Ok(()));
+        assert_eq!(
+            (stat.mode, stat.perm, stat.size),
+            (INodeType::IFREG, 0o640, 5)
+        );
+        assert_eq!(
+            test_vfs_open(
+                "/open_file",
+                FileMode::O_RDWR | FileMode::O_CREAT | FileMode::O_EXCL
+            )
+            .map(|_| ()),
+            Err(Error::new(EEXIST))
+        );
+        let file = test_vfs_open("/open_file", FileMode::O_RDWR | FileMode::O_CREAT).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/open_file", &mut stat), Ok(()));
+        assert_eq!(stat.size, 5);
+        set_time(3000);
+        let file = test_vfs_open("/open_file", FileMode::O_WRONLY | FileMode::O_TRUNC).unwrap();
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.size, stat.mtime, stat.ctime), (0, 3000, 3000));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* O_CREAT through a dangling link creates the target */
+        assert_eq!(test_vfs_symlink("/open_target", "/open_link"), Ok(()));
+        assert_eq!(
+            test_vfs_open(
+                "/open_link",
+                FileMode::O_RDWR | FileMode::O_CREAT | FileMode::O_EXCL
+            )
+            .map(|_| ()),
+            Err(Error::new(EEXIST))
+        );
+        let file = test_vfs_open("/open_link", FileMode::O_WRONLY | FileMode::O_CREAT).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_stat("/open_target", &mut stat), Ok(()));
+        assert_eq!(stat.mode, INodeType::IFREG);
+        assert_eq!(
+            test_vfs_open("/no_dir/open_file", FileMode::O_RDWR | FileMode::O_CREAT).map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* the access mode must fit the type of the inode */
+        assert_eq!(
+            test_vfs_open("/abc", FileMode::O_RDONLY | FileMode::O_CREAT).map(|_| ()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_open("/open_dir/", FileMode::O_RDONLY | FileMode::O_CREAT).map(|_| ()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_open("/abc", FileMode::O_WRONLY).map(|_| ()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_open("/abc", FileMode::O_RDONLY | FileMode::O_TRUNC).map(|_| ()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_open("/open_file/", FileMode::O_RDONLY).map(|_| ()),
+            Err(Error::new(ENOTDIR))
+        );
+        let file = test_vfs_open("/abc/", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* truncation needs write permission */
+        set_cred(user.clone());
+        assert_eq!(
+            test_vfs_open("/dac/root_file", FileMode::O_RDONLY | FileMode::O_TRUNC).map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            test_vfs_open("/dac/open_file", FileMode::O_RDWR | FileMode::O_CREAT).map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        /* a new file can be opened even if its mode denies the access */
+        let file = test_vfs_open_mode(
+            "/dac/sticky/open_file",
+            FileMode::O_RDWR | FileMode::O_CREAT,
+            mode(0o444),
+        )
+        .unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        set_cred(Credentials::root());
+
+        // test for fdtable
+        let mut fdt = FdTable::new();
+        assert_eq!(test_vfs_create("/fd_file"), Ok(()));
+        let file = test_vfs_open("/fd_file", FileMode::O_RDWR).unwrap();
+        let file2 = test_vfs_open("/fd_file", FileMode::O_RDONLY | FileMode::O_CLOEXEC).unwrap();
+        assert_eq!(fdt.install(file.clone()), Ok(0));
+        assert_eq!(fdt.install(file2.clone()), Ok(1));
+        assert_eq!(fdt.get_flags(0), Ok(FdFlag::empty()));
+        assert_eq!(fdt.get_flags(1), Ok(FdFlag::FD_CLOEXEC));
+        assert_eq!(fdt.dup(1), Ok(2));
+        assert!(Arc::ptr_eq(&fdt.get(2).unwrap(), &file2));
+        assert_eq!(fdt.get_flags(2), Ok(FdFlag::empty()));
+        assert_eq!(file2.read().ref_count, 2);
+        assert_eq!(fdt.get(3).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(fdt.dup(3), Err(Error::new(EBADF)));
+        /* the lowest free descriptor is reused */
+        assert_eq!(fdt.close(&mut REGISTERED_FS.lock(), 0), Ok(()));
+        assert_eq!(
+            fdt.close(&mut REGISTERED_FS.lock(), 0),
+            Err(Error::new(EBADF))
+        );
+        assert_eq!(file.read().ref_count, 0);
+        assert_eq!(fdt.install(file.clone()), Ok(0));
+        assert_eq!(fdt.dup2(&mut REGISTERED_FS.lock(), 0, 0), Ok(0));
+        assert_eq!(
+            fdt.dup2(&mut REGISTERED_FS.lock(), 3, 3),
+            Err(Error::new(EBADF))
+        );
+        assert_eq!(fdt.dup2(&mut REGISTERED_FS.lock(), 0, 5), Ok(5));
+        assert_eq!(fdt.get(4).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(fdt.dup(0), Ok(3));
+        assert_eq!(file.read().ref_count, 3);
+        /* dup2 closes the target silently */
+        assert_eq!(fdt.dup2(&mut REGISTERED_FS.lock(), 1, 5), Ok(5));
+        assert_eq!((file.read().ref_count, file2.read().ref_count), (2, 3));
+        assert_eq!(
+            fdt.dup3(&mut REGISTERED_FS.lock(), 5, 5, FdFlag::empty()),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            fdt.dup3(&mut REGISTERED_FS.lock(), 0, 5, FdFlag::FD_CLOEXEC),
+            Ok(5)
+        );
+        assert_eq!(fdt.get_flags(5), Ok(FdFlag::FD_CLOEXEC));
+        assert_eq!(
+            fdt.dup2(&mut REGISTERED_FS.lock(), 0, NR_OPEN),
+            Err(Error::new(EBADF))
+        );
+        assert_eq!(fdt.set_flags(3, FdFlag::FD_CLOEXEC), Ok(()));
+        assert_eq!(fdt.set_flags(4, FdFlag::FD_CLOEXEC), Err(Error::new(EBADF)));
+        /* a forked table shares the open file descriptions */
+        let mut child = fdt.fork();
+        assert_eq!((file.read().ref_count, file2.read().ref_count), (6, 4));
+        assert_eq!(
+            test_vfs_write(&child.get(0).unwrap(), &data1),
+            Ok(data1.len())
+        );
+        assert_eq!(fdt.get(3).unwrap().read().pos, data1.len());
+        assert_eq!(child.close_on_exec(&mut REGISTERED_FS.lock()), Ok(()));
+        assert_eq!(child.get(0).map(|_| ()), Ok(()));
+        assert_eq!(child.get(1).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(child.get(2).map(|_| ()), Ok(()));
+        assert_eq!(child.get(3).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(child.get(5).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!((file.read().ref_count, file2.read().ref_count), (4, 3));
+        assert_eq!(child.close_all(&mut REGISTERED_FS.lock()), Ok(()));
+        assert_eq!(fdt.get(3).map(|_| ()), Ok(()));
+        assert_eq!(fdt.close_all(&mut REGISTERED_FS.lock()), Ok(()));
+        assert_eq!((file.read().ref_count, file2.read().ref_count), (0, 0));
+        assert_eq!(test_vfs_unlink("/fd_file"), Ok(()));
+
+        // test for vfs_lseek
+        assert_eq!(test_vfs_create("/seek_file"), Ok(()));
+        let file = test_vfs_open("/seek_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &data2), Ok(data2.len()));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Set), Ok(0));
+        assert_eq!(test_vfs_read(&file, &mut buf[0..3]), Ok(3));
+        assert_eq!(buf[0..3], data2[0..3]);
+        assert_eq!(test_vfs_lseek(&file, -1, Whence::Cur), Ok(2));
+        assert_eq!(
+            test_vfs_lseek(&file, -7, Whence::End),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_lseek(&file, -1, Whence::Set),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(2));
+        assert_eq!(test_vfs_lseek(&file, -6, Whence::End), Ok(0));
+        /* seeking past the end and writing leaves a zero-filled gap */
+        assert_eq!(test_vfs_lseek(&file, 4, Whence::End), Ok(10));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 15);
+        assert_eq!(test_vfs_lseek(&file, 6, Whence::Set), Ok(6));
+        assert_eq!(test_vfs_read(&file, &mut buf[0..4]), Ok(4));
+        assert_eq!(buf[0..4], [0; 4]);
+        assert_eq!(test_vfs_lseek(&file, 3, Whence::Data), Ok(3));
+        assert_eq!(test_vfs_lseek(&file, 3, Whence::Hole), Ok(15));
+        assert_eq!(
+            test_vfs_lseek(&file, 15, Whence::Data),
+            Err(Error::new(ENXIO))
+        );
+        assert_eq!(
+            test_vfs_lseek(&file, 15, Whence::Hole),
+            Err(Error::new(ENXIO))
+        );
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(15));
+        /* a write beyond the largest file size fails instead of growing the file */
+        assert_eq!(
+            test_vfs_lseek(&file, isize::MAX, Whence::Set),
+            Ok(isize::MAX as usize)
+        );
+        assert_eq!(test_vfs_write(&file, &data1), Err(Error::new(EFBIG)));
+        assert_eq!(
+            test_vfs_pwrite(&file, &data1, usize::MAX - 1),
+            Err(Error::new(EFBIG))
+        );
+        assert_eq!(test_vfs_ftruncate(&file, 1 << 40), Err(Error::new(EFBIG)));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 15);
+        assert_eq!(test_vfs_lseek(&file, 15, Whence::Set), Ok(15));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* the offset of a directory is the index of an entry */
+        assert_eq!(test_vfs_mkdir("/seek_dir"), Ok(()));
+        assert_eq!(test_vfs_create("/seek_dir/a"), Ok(()));
+        assert_eq!(test_vfs_create("/seek_dir/b"), Ok(()));
+        let file = test_vfs_open("/seek_dir", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(3));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(3));
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(1));
+        assert_eq!(&dirs[0].name[0..dirs[0].name_len], "b".as_bytes());
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Set), Ok(0));
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(3));
+        assert_eq!(&dirs[0].name[0..dirs[0].name_len], ".".as_bytes());
+        assert_eq!(test_vfs_lseek(&file, -1, Whence::Cur), Ok(2));
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(2));
+        assert_eq!(&dirs[0].name[0..dirs[0].name_len], "a".as_bytes());
+        assert_eq!(dirs[0].off, 2);
+        assert_eq!(test_vfs_lseek(&file, 10, Whence::Set), Ok(10));
+        assert_eq!(test_vfs_readdir(&file, &mut dirs), Ok(0));
+        assert_eq!(
+            test_vfs_lseek(&file, 0, Whence::End),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_lseek(&file, 0, Whence::Data),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+
+        // test for vfs_pread, vfs_pwrite, vfs_readv and vfs_writev
+        assert_eq!(test_vfs_create("/pio_file"), Ok(()));
+        let file = test_vfs_open("/pio_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_pwrite(&file, &data2, 4), Ok(data2.len()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 10);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..6], 4), Ok(6));
+        assert_eq!(buf[0..6], data2[..]);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..4], 0), Ok(4));
+        assert_eq!(buf[0..4], [0; 4]);
+        assert_eq!(test_vfs_pread(&file, &mut buf, 10), Ok(0));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(0));
+        assert_eq!(test_vfs_writev(&file, &[&data1, &data2]), Ok(11));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(11));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Set), Ok(0));
+        let (mut iov1, mut iov2) = ([0u8; 5], [0u8; 10]);
+        assert_eq!(test_vfs_readv(&file, &mut [&mut iov1, &mut iov2]), Ok(11));
+        assert_eq!(iov1[..], data1[..]);
+        assert_eq!(iov2[0..6], data2[..]);
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(11));
+        assert_eq!(test_vfs_readv(&file, &mut [&mut iov1, &mut iov2]), Ok(0));
+        /* positional i/o does not need the lock of the file */
+        {
+            let fop = file.read().fop.clone();
+            let _guard = file.write();
+            assert_eq!(fop.read_at(&file, &mut buf[0..5], 0), Ok(5));
+            assert_eq!(fop.write_at(&file, &data1, 11), Ok(5));
+        }
+        assert_eq!(buf[0..5], data1);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* pwrite ignores O_APPEND, while writev appends */
+        let file = test_vfs_open("/pio_file", FileMode::O_WRONLY | FileMode::O_APPEND).unwrap();
+        assert_eq!(test_vfs_pwrite(&file, &data2, 0), Ok(data2.len()));
+        assert_eq!(test_vfs_writev(&file, &[&data1]), Ok(data1.len()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 21);
+        assert_eq!(test_vfs_pread(&file, &mut buf, 0), Err(Error::new(EBADF)));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let file = test_vfs_open("/pio_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_pwrite(&file, &data1, 0), Err(Error::new(EBADF)));
+        assert_eq!(test_vfs_writev(&file, &[&data1]), Err(Error::new(EBADF)));
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..6], 0), Ok(6));
+        assert_eq!(buf[0..6], data2[..]);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 16), Ok(5));
+        assert_eq!(buf[0..5], data1);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+
+        // test for read and write boundaries
+        assert_eq!(test_vfs_create("/eof_file"), Ok(()));
+        let file = test_vfs_open("/eof_file", FileMode::O_RDWR).unwrap();
+        /* reading an empty file hits EOF at once */
+        assert_eq!(test_vfs_read(&file, &mut buf), Ok(0));
+        assert_eq!(test_vfs_write(&file, &data2), Ok(data2.len()));
+        assert_eq!(test_vfs_read(&file, &mut buf), Ok(0));
+        /* zero-length buffers transfer nothing and leave the file unchanged */
+        set_time(4000);
+        assert_eq!(test_vfs_write(&file, &[]), Ok(0));
+        assert_eq!(test_vfs_pwrite(&file, &[], 100), Ok(0));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, data2.len());
+        assert_ne!(stat.mtime, 4000);
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Set), Ok(0));
+        assert_eq!(test_vfs_read(&file, &mut buf[0..0]), Ok(0));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(0));
+        /* a short read returns exactly what is left, then EOF */
+        assert_eq!(test_vfs_lseek(&file, 4, Whence::Set), Ok(4));
+        assert_eq!(test_vfs_read(&file, &mut buf), Ok(2));
+        assert_eq!(buf[0..2], data2[4..6]);
+        assert_eq!(test_vfs_read(&file, &mut buf), Ok(0));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(6));
+        /* reading past EOF returns 0 without moving the position */
+        assert_eq!(test_vfs_lseek(&file, 100, Whence::Set), Ok(100));
+        assert_eq!(test_vfs_read(&file, &mut buf), Ok(0));
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Cur), Ok(100));
+        assert_eq!(test_vfs_pread(&file, &mut buf, 6), Ok(0));
+        /* a `cat`-style loop terminates */
+        assert_eq!(test_vfs_lseek(&file, 0, Whence::Set), Ok(0));
+        let mut content = Vec::new();
+        loop {
+            let len = test_vfs_read(&file, &mut buf[0..4]).unwrap();
+            if len == 0 {
+                break;
+            }
+            content.extend_from_slice(&buf[0..len]);
+        }
+        assert_eq!(content, data2);
+        /* writes past EOF create a zero-filled gap */
+        assert_eq!(test_vfs_lseek(&file, 10, Whence::Set), Ok(10));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_pwrite(&file, &data1, 20), Ok(data1.len()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 25);
+        assert_eq!(test_vfs_pread(&file, &mut buf, 0), Ok(buf.len()));
+        assert_eq!(buf[0..6], data2[..]);
+        assert_eq!(buf[6..10], [0; 4]);
+        assert_eq!(buf[10..15], data1[..]);
+        assert_eq!(buf[15..20], [0; 5]);
+        assert_eq!(test_vfs_pread(&file, &mut buf, 20), Ok(5));
+        assert_eq!(buf[0..5], data1[..]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* every O_APPEND write lands at the end, even with several writers */
+        assert_eq!(test_vfs_create("/append_file"), Ok(()));
+        let appender = test_vfs_open("/append_file", FileMode::O_WRONLY | FileMode::O_APPEND);
+        let appender = appender.unwrap();
+        let writer = test_vfs_open("/append_file", FileMode::O_WRONLY).unwrap();
+        assert_eq!(test_vfs_write(&writer, &data2), Ok(data2.len()));
+        assert_eq!(test_vfs_write(&appender, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_lseek(&appender, 0, Whence::Cur), Ok(11));
+        assert_eq!(test_vfs_lseek(&appender, 0, Whence::Set), Ok(0));
+        assert_eq!(test_vfs_write(&appender, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_fstat(&writer, &mut stat), Ok(()));
+        assert_eq!(stat.size, 16);
+        assert_eq!(test_vfs_close(&appender), Ok(()));
+        assert_eq!(test_vfs_close(&writer), Ok(()));
+        let threads: Vec<_> = (1..=4u8)
+            .map(|id| {
+                std::thread::spawn(move || {
+                    let file =
+                        test_vfs_open("/append_file", FileMode::O_WRONLY | FileMode::O_APPEND)
+                            .unwrap();
+                    for _ in 0..50 {
+                        assert_eq!(test_vfs_write(&file, &[id; 8]), Ok(8));
+                    }
+                    assert_eq!(test_vfs_close(&file), Ok(()));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let file = test_vfs_open("/append_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.size, 16 + 4 * 50 * 8);
+        let mut record = [0u8; 8];
+        let mut counts = [0; 5];
+        assert_eq!(test_vfs_lseek(&file, 16, Whence::Set), Ok(16));
+        while test_vfs_read(&file, &mut record) == Ok(8) {
+            /* records from different writers never interleave */
+            assert!(record.iter().all(|byte| *byte == record[0]));
+            counts[record[0] as usize] += 1;
+        }
+        assert_eq!(counts, [0, 50, 50, 50, 50]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+
+        // test for unlinking opened files
+        assert_eq!(test_vfs_create("/orphan"), Ok(()));
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/orphan")
+            .unwrap();
+        let file = test_vfs_open("/orphan", FileMode::O_RDWR).unwrap();
+        let file2 = test_vfs_open("/orphan", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_unlink("/orphan"), Ok(()));
+        assert_eq!(test_vfs_lookup("/orphan"), Err(Error::new(ENOENT)));
+        /* the open file keeps working without a name */
+        assert_eq!(test_vfs_write(&file, &data2), Ok(data2.len()));
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..11], 0), Ok(11));
+        assert_eq!(buf[0..5], data1[..]);
+        assert_eq!(buf[5..11], data2[..]);
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.nlink, stat.size), (0, 11));
+        /* the name can be reused by another file */
+        assert_eq!(test_vfs_create("/orphan"), Ok(()));
+        assert_eq!(test_vfs_stat("/orphan", &mut stat2), Ok(()));
+        assert_ne!(stat2.ino, stat.ino);
+        assert_eq!(test_vfs_unlink("/orphan"), Ok(()));
+        /* the inode is freed with the last open file */
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert!(dentry.read().get_inode().is_ok());
+        drop(file);
+        assert_eq!(test_vfs_close(&file2), Ok(()));
+        drop(file2);
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* an unopened file is freed by unlink at once, unless it has another link */
+        assert_eq!(test_vfs_create("/orphan"), Ok(()));
+        assert_eq!(test_vfs_link("/orphan", "/orphan_link"), Ok(()));
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/orphan")
+            .unwrap();
+        assert_eq!(test_vfs_unlink("/orphan"), Ok(()));
+        assert!(dentry.read().get_inode().is_ok());
+        assert_eq!(test_vfs_unlink("/orphan_link"), Ok(()));
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* the same goes for a file replaced by rename, and for a directory */
+        assert_eq!(test_vfs_create("/orphan"), Ok(()));
+        assert_eq!(test_vfs_create("/orphan_new"), Ok(()));
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/orphan_new")
+            .unwrap();
+        let file = test_vfs_open("/orphan_new", FileMode::O_RDWR).unwrap();
+        assert_eq!(
+            test_vfs_rename("/orphan", "/orphan_new", RenameFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        let mut fdt = FdTable::new();
+        assert_eq!(fdt.install(file), Ok(0));
+        assert_eq!(fdt.close(&mut REGISTERED_FS.lock(), 0), Ok(()));
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_unlink("/orphan_new"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/orphan_dir"), Ok(()));
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/orphan_dir")
+            .unwrap();
+        let file = test_vfs_open("/orphan_dir", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_unlink("/orphan_dir"), Ok(()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(stat.nlink, 0);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* a stale handle to a freed inode gives an error instead of a panic */
+        let mut statfs = StatFs::default();
+        let mut statfs2 = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_statfs(&current_context(), &current_cred(), "/", &mut statfs),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/orphan"), Ok(()));
+        let file = test_vfs_open("/orphan", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_unlink("/orphan"), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Err(Error::new(ENOENT)));
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_statfs(&current_context(), &current_cred(), "/", &mut statfs2),
+            Ok(())
+        );
+        assert_eq!(statfs2.files, statfs.files);
+        drop(file);
+
+        // test for vfs_tmpfile and vfs_link_file
+        assert_eq!(test_vfs_mkdir("/tmp_dir"), Ok(()));
+        let file = test_vfs_tmpfile(
+            "/tmp_dir",
+            FileMode::O_RDWR,
+            INodePerm::from_bits_truncate(0o666),
+        )
+        .unwrap();
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!((stat.mode, stat.nlink, stat.size), (INodeType::IFREG, 0, 0));
+        assert_eq!(stat.perm, 0o644);
+        /* the file has no name in the directory */
+        let dir = test_vfs_open("/tmp_dir", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_readdir(&dir, &mut dirs), Ok(2));
+        assert_eq!(test_vfs_close(&dir), Ok(()));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_link_file(&file, "/tmp_dir/named"), Ok(()));
+        assert_eq!(test_vfs_stat("/tmp_dir/named", &mut stat2), Ok(()));
+        assert_eq!((stat2.ino, stat2.nlink, stat2.size), (stat.ino, 1, 5));
+        assert_eq!(
+            test_vfs_link_file(&file, "/tmp_dir/named"),
+            Err(Error::new(EEXIST))
+        );
+        /* a linked file survives the close, and can be given more names */
+        assert_eq!(test_vfs_link_file(&file, "/tmp_dir/named2"), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        let file = test_vfs_open("/tmp_dir/named2", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..5]), Ok(5));
+        assert_eq!(buf[0..5], data1[..]);
+        /* an unlinked file can not be linked again */
+        assert_eq!(test_vfs_unlink("/tmp_dir/named"), Ok(()));
+        assert_eq!(test_vfs_unlink("/tmp_dir/named2"), Ok(()));
+        assert_eq!(
+            test_vfs_link_file(&file, "/tmp_dir/named"),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* O_EXCL forbids linking, and the file is freed with the close */
+        let file = test_vfs_tmpfile(
+            "/tmp_dir",
+            FileMode::O_RDWR | FileMode::O_EXCL,
+            INodePerm::from_bits_truncate(0o600),
+        )
+        .unwrap();
+        let dentry = file.read().dentry.clone();
+        assert_eq!(
+            test_vfs_link_file(&file, "/tmp_dir/named"),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* invalid arguments */
+        assert_eq!(
+            test_vfs_tmpfile(
+                "/tmp_dir",
+                FileMode::O_RDONLY,
+                INodePerm::from_bits_truncate(0o600)
+            )
+            .map(|_| ()),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(test_vfs_create("/tmp_dir/reg"), Ok(()));
+        assert_eq!(
+            test_vfs_tmpfile(
+                "/tmp_dir/reg",
+                FileMode::O_RDWR,
+                INodePerm::from_bits_truncate(0o600)
+            )
+            .map(|_| ()),
+            Err(Error::new(ENOTDIR))
+        );
+        assert_eq!(
+            test_vfs_tmpfile(
+                "/tmp_dir/none",
+                FileMode::O_RDWR,
+                INodePerm::from_bits_truncate(0o600)
+            )
+            .map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        /* creating in the directory needs write permission */
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(
+            test_vfs_tmpfile(
+                "/tmp_dir",
+                FileMode::O_RDWR,
+                INodePerm::from_bits_truncate(0o600)
+            )
+            .map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        set_cred(Credentials::root());
+
+        // test for file operations
+        /* the file operations are chosen by the type of the inode */
+        assert_eq!(test_vfs_create("/fop_file"), Ok(()));
+        let file = test_vfs_open("/fop_file", FileMode::O_RDWR).unwrap();
+        let dir = test_vfs_open("/", FileMode::O_RDONLY).unwrap();
+        let dir_fop = dir.read().fop.clone();
+        assert_eq!(dir_fop.read(&dir, &mut buf[0..1]), Err(Error::new(EINVAL)));
+        let file_fop = file.read().fop.clone();
+        assert_eq!(file_fop.readdir(&file, &mut dirs), Err(Error::new(ENOTDIR)));
+        assert_eq!(test_vfs_close(&dir), Ok(()));
+        /* a pseudo file with its own operations on top of the same inode */
+        let ops = Arc::new(CounterOps::default());
+        let (mnt, dentry, inode) = {
+            let fr = file.read();
+            (fr.mnt.clone(), fr.dentry.clone(), fr.inode.clone())
+        };
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let pseudo = Arc::new(RwLock::new(File::new(
+            "/fop_file".to_string(),
+            0,
+            0,
+            mnt,
+            dentry,
+            inode.clone(),
+            ops.clone(),
+            FileMode::O_RDWR,
+        )));
+        assert_eq!(
+            test_vfs_read(&pseudo, &mut buf[0..1]),
+            Err(Error::new(EBADF))
+        );
+        assert_eq!(ops.open(&inode, &pseudo), Ok(()));
+        assert_eq!(test_vfs_read(&pseudo, &mut buf[0..1]), Ok(1));
+        assert_eq!(test_vfs_read(&pseudo, &mut buf[0..1]), Ok(1));
+        assert_eq!(buf[0], 2);
+        /* operations which are not provided fail, and llseek is generic */
+        assert_eq!(test_vfs_write(&pseudo, &data1), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_lseek(&pseudo, 3, Whence::Set), Ok(3));
+        /* every close of a descriptor flushes, the last one releases */
+        let mut fdt = FdTable::new();
+        assert_eq!(fdt.install(pseudo.clone()), Ok(0));
+        assert_eq!(fdt.dup(0), Ok(1));
+        assert_eq!(fdt.close(&mut REGISTERED_FS.lock(), 1), Ok(()));
+        assert_eq!(
+            (
+                ops.flushed.load(Ordering::SeqCst),
+                ops.released.load(Ordering::SeqCst)
+            ),
+            (1, 0)
+        );
+        assert_eq!(fdt.close(&mut REGISTERED_FS.lock(), 0), Ok(()));
+        assert_eq!(
+            (
+                ops.flushed.load(Ordering::SeqCst),
+                ops.released.load(Ordering::SeqCst)
+            ),
+            (2, 1)
+        );
+        /* a failing close does not stop the others, the first error is returned */
+        ops.fail_flush.store(true, Ordering::SeqCst);
+        assert_eq!(fdt.install(pseudo.clone()), Ok(0));
+        assert_eq!(fdt.dup(0), Ok(1));
+        assert_eq!(fdt.set_flags(0, FdFlag::FD_CLOEXEC), Ok(()));
+        assert_eq!(fdt.set_flags(1, FdFlag::FD_CLOEXEC), Ok(()));
+        assert_eq!(
+            fdt.close_on_exec(&mut REGISTERED_FS.lock()),
+            Err(Error::new(EIO))
+        );
+        assert_eq!(fdt.get(0).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(fdt.get(1).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(fdt.install(pseudo.clone()), Ok(0));
+        assert_eq!(fdt.dup(0), Ok(1));
+        assert_eq!(
+            fdt.close_all(&mut REGISTERED_FS.lock()),
+            Err(Error::new(EIO))
+        );
+        assert_eq!(fdt.get(1).map(|_| ()), Err(Error::new(EBADF)));
+        assert_eq!(pseudo.read().ref_count, 0);
+        ops.fail_flush.store(false, Ordering::SeqCst);
+        /* a failing release still frees an unlinked inode */
+        let file = test_vfs_open("/fop_file", FileMode::O_RDWR).unwrap();
+        file.write().fop = ops.clone();
+        assert_eq!(test_vfs_unlink("/fop_file"), Ok(()));
+        ops.fail_release.store(true, Ordering::SeqCst);
+        assert_eq!(test_vfs_close(&file), Err(Error::new(EIO)));
+        ops.fail_release.store(false, Ordering::SeqCst);
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Err(Error::new(ENOENT)));
+
+        // test for vfs_fsync, vfs_fdatasync, vfs_syncfs and vfs_sync
+        assert_eq!(test_vfs_create("/sync_file"), Ok(()));
+        let file = test_vfs_open("/sync_file", FileMode::O_WRONLY).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(REGISTERED_FS.lock().vfs_fsync(&file), Ok(()));
+        assert_eq!(REGISTERED_FS.lock().vfs_fdatasync(&file), Ok(()));
+        assert_eq!(REGISTERED_FS.lock().vfs_syncfs(&file), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        /* a read-only file and a directory can be synced as well */
+        let file = test_vfs_open("/sync_file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(REGISTERED_FS.lock().vfs_fsync(&file), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let dir = test_vfs_open("/", FileMode::O_RDONLY).unwrap();
+        assert_eq!(REGISTERED_FS.lock().vfs_fsync(&dir), Ok(()));
+        assert_eq!(test_vfs_close(&dir), Ok(()));
+        /* file operations without fsync can not be synced */
+        let file = test_vfs_open("/sync_file", FileMode::O_RDONLY).unwrap();
+        let (mnt, dentry, inode) = {
+            let fr = file.read();
+            (fr.mnt.clone(), fr.dentry.clone(), fr.inode.clone())
+        };
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let pseudo = Arc::new(RwLock::new(File::new(
+            "/sync_file".to_string(),
+            0,
+            0,
+            mnt,
+            dentry,
+            inode,
+            Arc::new(CounterOps::default()),
+            FileMode::O_RDONLY,
+        )));
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_fsync(&pseudo),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_fdatasync(&pseudo),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(REGISTERED_FS.lock().vfs_sync(), Ok(()));
+        assert_eq!(test_vfs_unlink("/sync_file"), Ok(()));
+
+        // test for the superblock and vfs_statfs
+        let mut statfs = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_statfs(&current_context(), &current_cred(), "/", &mut statfs),
+            Ok(())
+        );
+        assert_eq!((statfs.bsize, statfs.namelen), (4096, 255));
+        assert_eq!(statfs.flags, MountFlag::empty());
+        assert_eq!(test_vfs_create("/sb_file"), Ok(()));
+        let file = test_vfs_open("/sb_file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_pwrite(&file, &data1, 5000), Ok(data1.len()));
+        let mut statfs2 = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_fstatfs(&file, &mut statfs2),
+            Ok(())
+        );
+        assert_eq!(
+            (statfs2.files, statfs2.blocks),
+            (statfs.files + 1, statfs.blocks + 2)
+        );
+        /* inodes can be reached through the superblock */
+        let fs = file.read().inode.get_fs();
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!(fs.get_root().map(|inode| inode.get_ino()), Ok(stat.ino));
+        assert_eq!(test_vfs_fstat(&file, &mut stat), Ok(()));
+        assert_eq!(
+            fs.get_inode(stat.ino).map(|inode| inode.get_ino()),
+            Ok(stat.ino)
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        let inode = fs
+            .alloc_inode(INodeMetaData::new(INodeType::IFREG))
+            .unwrap();
+        let ino = inode.get_ino();
+        assert_ne!(ino, stat.ino);
+        assert_eq!(inode.get_metadata().unwrap().nlink, 0);
+        assert_eq!(fs.evict_inode(&inode), Ok(()));
+        assert_eq!(fs.get_inode(ino).map(|_| ()), Err(Error::new(ENOENT)));
+        /* remount changes the flags reported by statfs */
+        assert_eq!(
+            fs.remount(&MountOptions::parse(MountFlag::MS_RDONLY, "").unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_statfs(
+                &current_context(),
+                &current_cred(),
+                "/sb_file",
+                &mut statfs
+            ),
+            Ok(())
+        );
+        assert_eq!(statfs.flags, MountFlag::MS_RDONLY);
+        assert_eq!(fs.remount(&MountOptions::default()), Ok(()));
+        assert_eq!(test_vfs_unlink("/sb_file"), Ok(()));
+        /* nothing of a filesystem is left after put_super */
+        let (other_fs, _other_root) = REGISTERED_FS
+            .lock()
+            .mount_fs(FSType::RAMFS, "", &MountOptions::default())
+            .unwrap();
+        let root_ino = other_fs.get_root().unwrap().get_ino();
+        assert_eq!(other_fs.put_super(), Ok(()));
+        assert_eq!(other_fs.get_root().map(|_| ()), Err(Error::new(ENOENT)));
+        assert_eq!(
+            other_fs.get_inode(root_ino).map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+
+        // test for vfs_mount
+        assert_eq!(test_vfs_mkdir("/mnt"), Ok(()));
+        assert_eq!(test_vfs_create("/mnt/hidden"), Ok(()));
+        assert_eq!(test_vfs_stat("/mnt", &mut stat), Ok(()));
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(
+            test_vfs_mount("/mnt", MountFlag::empty(), ""),
+            Err(Error::new(EPERM))
+        );
+        set_cred(Credentials::root());
+        assert_eq!(
+            test_vfs_mount("/mnt/hidden", MountFlag::empty(), ""),
+            Err(Error::new(ENOTDIR))
+        );
+        assert_eq!(
+            test_vfs_mount("/mnt/none", MountFlag::empty(), ""),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_mount("/mnt", MountFlag::empty(), ""), Ok(()));
+        /* the mounted root covers the directory */
+        assert_eq!(test_vfs_stat("/mnt", &mut stat2), Ok(()));
+        assert_ne!(stat2.ino, stat.ino);
+        assert_eq!(test_vfs_lookup("/mnt/hidden"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_mkdir("/mnt/inner"), Ok(()));
+        assert_eq!(test_vfs_create("/mnt/inner/file"), Ok(()));
+        let mut statfs = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_statfs(
+                &current_context(),
+                &current_cred(),
+                "/mnt/inner/file",
+                &mut statfs
+            ),
+            Ok(())
+        );
+        assert_eq!(statfs.files, 3);
+        /* `..` leads back to the mounting filesystem */
+        assert_eq!(test_vfs_stat("/", &mut stat), Ok(()));
+        assert_eq!(test_vfs_stat("/mnt/..", &mut stat2), Ok(()));
+        assert_eq!(stat2.ino, stat.ino);
+        assert_eq!(
+            test_vfs_stat("/mnt/inner/../../mnt/inner/file", &mut stat2),
+            Ok(())
+        );
+        assert_eq!(test_vfs_chdir("/mnt/inner"), Ok(()));
+        assert_eq!(test_vfs_lookup("file"), Ok(()));
+        assert_eq!(test_vfs_chdir("../.."), Ok(()));
+        assert_eq!(test_vfs_lookup("mnt/inner/file"), Ok(()));
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
+        assert_eq!(test_vfs_symlink("/mnt/inner", "/mnt_link"), Ok(()));
+        assert_eq!(test_vfs_lookup("/mnt_link/file"), Ok(()));
+        /* links and renames do not cross mounts */
+        assert_eq!(
+            test_vfs_link("/mnt/inner/file", "/file_link"),
+            Err(Error::new(EXDEV))
+        );
+        assert_eq!(
+            test_vfs_rename("/mnt/inner/file", "/file_moved", RenameFlag::empty()),
+            Err(Error::new(EXDEV))
+        );
+        /* a mountpoint is busy */
+        assert_eq!(test_vfs_unlink("/mnt"), Err(Error::new(EBUSY)));
+        assert_eq!(
+            test_vfs_rename("/mnt", "/mnt_moved", RenameFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(test_vfs_mkdir("/mnt_other"), Ok(()));
+        assert_eq!(
+            test_vfs_rename("/mnt_other", "/mnt", RenameFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        /* a second mount on the same directory hides the first one */
+        assert_eq!(test_vfs_mount("/mnt", MountFlag::empty(), ""), Ok(()));
+        assert_eq!(test_vfs_lookup("/mnt/inner"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_stat("/mnt/..", &mut stat2), Ok(()));
+        assert_eq!(stat2.ino, stat.ino);
+
+        // test for vfs_umount
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(
+            test_vfs_umount("/mnt", UmountFlag::empty()),
+            Err(Error::new(EPERM))
+        );
+        set_cred(Credentials::root());
+        assert_eq!(
+            test_vfs_umount("/", UmountFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(
+            test_vfs_umount("/mnt_other", UmountFlag::empty()),
+            Err(Error::new(EINVAL))
+        );
+        /* the cwd of a task keeps the mount busy */
+        assert_eq!(test_vfs_chdir("/mnt"), Ok(()));
+        assert_eq!(
+            test_vfs_umount("/mnt", UmountFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
+        let fs = Arc::downgrade(
+            &REGISTERED_FS
+                .lock()
+                .vfs_lookup(&current_context(), &current_cred(), "/mnt")
+                .unwrap()
+                .read()
+                .get_inode()
+                .unwrap()
+                .get_fs(),
+        );
+        /* a dentry held without its mount does not */
+        let dentry = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mnt")
+            .unwrap();
+        assert_eq!(test_vfs_umount("/mnt", UmountFlag::empty()), Ok(()));
+        assert!(fs.upgrade().is_none());
+        assert_eq!(
+            dentry.read().get_inode().map(|_| ()),
+            Err(Error::new(ENOENT))
+        );
+        drop(dentry);
+        /* the first mount is uncovered */
+        assert_eq!(test_vfs_lookup("/mnt/inner/file"), Ok(()));
+        assert_eq!(
+            test_vfs_umount("/mnt/inner", UmountFlag::empty()),
+            Err(Error::new(EINVAL))
+        );
+        /* so are open files and other mounts below it */
+        let file = test_vfs_open("/mnt/inner/file", FileMode::O_RDWR).unwrap();
+        assert_eq!(
+            test_vfs_umount("/mnt", UmountFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(test_vfs_mount("/mnt/inner", MountFlag::empty(), ""), Ok(()));
+        assert_eq!(
+            test_vfs_umount("/mnt", UmountFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        /* a lazy unmount detaches the whole tree, which lives on while it is used,
+         * other references to a filesystem do not keep it from being torn down */
+        let parent_fs = REGISTERED_FS
+            .lock()
+            .vfs_lookup(&current_context(), &current_cred(), "/mnt")
+            .unwrap()
+            .read()
+            .get_inode()
+            .unwrap()
+            .get_fs();
+        assert_eq!(test_vfs_create("/mnt/inner/child_file"), Ok(()));
+        let file = test_vfs_open("/mnt/inner/child_file", FileMode::O_RDWR).unwrap();
+        let child_fs = file.read().inode.get_fs();
+        assert_eq!(test_vfs_umount("/mnt", UmountFlag::MNT_DETACH), Ok(()));
+        assert_eq!(test_vfs_lookup("/mnt/hidden"), Ok(()));
+        assert_eq!(test_vfs_lookup("/mnt/inner"), Err(Error::new(ENOENT)));
+        /* put_super of the idle filesystem has run */
+        assert_eq!(parent_fs.get_root().map(|_| ()), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 0), Ok(5));
+        assert_eq!(buf[0..5], data1[..]);
+        assert_eq!(child_fs.get_root().map(|_| ()), Ok(()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* and so has the one of the filesystem whose last file is closed */
+        assert_eq!(child_fs.get_root().map(|_| ()), Err(Error::new(ENOENT)));
+        let mut statfs = StatFs::default();
+        assert_eq!(child_fs.statfs(&mut statfs), Ok(()));
+        assert_eq!(statfs.files, 0);
+        let weak_fs = Arc::downgrade(&child_fs);
+        drop(child_fs);
+        drop(parent_fs);
+        assert!(weak_fs.upgrade().is_none());
+        /* a symbolic link is followed unless UMOUNT_NOFOLLOW is given */
+        assert_eq!(test_vfs_mount("/mnt", MountFlag::empty(), ""), Ok(()));
+        assert_eq!(test_vfs_symlink("/mnt", "/mnt_alias"), Ok(()));
+        assert_eq!(
+            test_vfs_umount("/mnt_alias", UmountFlag::UMOUNT_NOFOLLOW),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(test_vfs_umount("/mnt_alias", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_lookup("/mnt/hidden"), Ok(()));
+
+        // test for mount flags and vfs_remount
+        assert_eq!(test_vfs_mkdir("/ro"), Ok(()));
+        assert_eq!(test_vfs_mount("/ro", MountFlag::empty(), ""), Ok(()));
+        assert_eq!(test_vfs_create("/ro/file"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/ro/dir"), Ok(()));
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(
+            test_vfs_remount("/ro", MountFlag::MS_RDONLY),
+            Err(Error::new(EPERM))
+        );
+        set_cred(Credentials::root());
+        assert_eq!(
+            test_vfs_remount("/ro/dir", MountFlag::MS_RDONLY),
+            Err(Error::new(EINVAL))
+        );
+        /* a file open for writing prevents going read only */
+        let file = test_vfs_open("/ro/file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(
+            test_vfs_remount("/ro", MountFlag::MS_RDONLY),
+            Err(Error::new(EBUSY))
+        );
+        /* the superblock itself can still go read only under an open file */
+        let fs = file.read().inode.get_fs();
+        assert_eq!(
+            fs.remount(&MountOptions::parse(MountFlag::MS_RDONLY, "").unwrap()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_ftruncate(&file, 0), Err(Error::new(EROFS)));
+        assert_eq!(fs.remount(&MountOptions::default()), Ok(()));
+        drop(fs);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(test_vfs_remount("/ro", MountFlag::MS_RDONLY), Ok(()));
+        let mut statfs = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_statfs(
+                &current_context(),
+                &current_cred(),
+                "/ro/file",
+                &mut statfs
+            ),
+            Ok(())
+        );
+        assert_eq!(statfs.flags, MountFlag::MS_RDONLY);
+        /* nothing can be changed under a read only mount */
+        assert_eq!(test_vfs_create("/ro/new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_mkdir("/ro/new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_symlink("file", "/ro/new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_link("/ro/file", "/ro/new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_unlink("/ro/file"), Err(Error::new(EROFS)));
+        assert_eq!(
+            test_vfs_rename("/ro/file", "/ro/new", RenameFlag::empty()),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_chmod("/ro/file", INodePerm::from_bits_truncate(0o600)),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_chown("/ro/file", Some(1000), None),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_utimensat("/ro/file", UTime::Now, UTime::Now),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(test_vfs_truncate("/ro/file", 0), Err(Error::new(EROFS)));
+        assert_eq!(
+            test_vfs_open("/ro/file", FileMode::O_RDWR).map(|_| ()),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_open("/ro/file", FileMode::O_RDONLY | FileMode::O_TRUNC).map(|_| ()),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_open("/ro/new", FileMode::O_RDWR | FileMode::O_CREAT).map(|_| ()),
+            Err(Error::new(EROFS))
+        );
+        assert_eq!(
+            test_vfs_tmpfile(
+                "/ro",
+                FileMode::O_RDWR,
+                INodePerm::from_bits_truncate(0o600)
+            )
+            .map(|_| ()),
+            Err(Error::new(EROFS))
+        );
+        /* reading works, without touching the access time */
+        set_time(10000);
+        assert_eq!(test_vfs_stat("/ro/file", &mut stat), Ok(()));
+        let file = test_vfs_open("/ro/file", FileMode::O_RDONLY | FileMode::O_CREAT).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..5]), Ok(5));
+        assert_eq!(buf[0..5], data1[..]);
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, stat.atime);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* MS_NOATIME */
+        assert_eq!(test_vfs_remount("/ro", MountFlag::MS_NOATIME), Ok(()));
+        assert_eq!(test_vfs_create("/ro/new"), Ok(()));
+        let file = test_vfs_open("/ro/file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..5]), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, stat.atime);
+        /* MS_RELATIME updates the access time only once after a change, or once a day */
+        assert_eq!(test_vfs_remount("/ro", MountFlag::MS_RELATIME), Ok(()));
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 0), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, 10000);
+        set_time(20000);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 0), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, 10000);
+        set_time(10000 + 24 * 60 * 60);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 0), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, 10000 + 24 * 60 * 60);
+        /* strictatime given later overrides relatime */
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_remount(
+                &current_context(),
+                &current_cred(),
+                "/ro",
+                MountFlag::empty(),
+                "relatime,strictatime",
+            ),
+            Ok(())
+        );
+        set_time(20000 + 24 * 60 * 60);
+        assert_eq!(test_vfs_pread(&file, &mut buf[0..5], 0), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, 20000 + 24 * 60 * 60);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* MS_NODIRATIME leaves the access time of directories only */
+        set_time(1000000);
+        assert_eq!(test_vfs_remount("/ro", MountFlag::MS_NODIRATIME), Ok(()));
+        let dir = test_vfs_open("/ro/dir", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_fstat(&dir, &mut stat), Ok(()));
+        assert_eq!(test_vfs_readdir(&dir, &mut dirs), Ok(2));
+        assert_eq!(test_vfs_fstat(&dir, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, stat.atime);
+        assert_eq!(test_vfs_close(&dir), Ok(()));
+        drop(dir);
+        let file = test_vfs_open("/ro/file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..5]), Ok(5));
+        assert_eq!(test_vfs_fstat(&file, &mut stat2), Ok(()));
+        assert_eq!(stat2.atime, 1000000);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* MS_NOEXEC */
+        assert_eq!(
+            test_vfs_chmod("/ro/file", INodePerm::from_bits_truncate(0o755)),
+            Ok(())
+        );
+        let file = REGISTERED_FS
+            .lock()
+            .vfs_open_exec(&current_context(), &current_cred(), "/ro/file")
+            .unwrap();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_exec_cred(&file, &Credentials::new(1000, 1000))
+                .map(|cred| (cred.uid, cred.gid)),
+            Ok((1000, 1000))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* set user id and set group id programs */
+        assert_eq!(test_vfs_chown("/ro/file", Some(1001), Some(1002)), Ok(()));
+        assert_eq!(
+            test_vfs_chmod("/ro/file", INodePerm::from_bits_truncate(0o6755)),
+            Ok(())
+        );
+        let file = REGISTERED_FS
+            .lock()
+            .vfs_open_exec(&current_context(), &current_cred(), "/ro/file")
+            .unwrap();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_exec_cred(&file, &Credentials::new(1000, 1000))
+                .map(|cred| (cred.uid, cred.gid)),
+            Ok((1001, 1002))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(
+            test_vfs_chmod("/ro/file", INodePerm::from_bits_truncate(0o6745)),
+            Ok(())
+        );
+        let file = test_vfs_open("/ro/file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_exec_cred(&file, &Credentials::new(1000, 1000))
+                .map(|cred| (cred.uid, cred.gid)),
+            Ok((1001, 1000))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(
+            test_vfs_chmod("/ro/file", INodePerm::from_bits_truncate(0o6755)),
+            Ok(())
+        );
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_open_exec(&current_context(), &current_cred(), "/ro/new")
+                .map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_open_exec(&current_context(), &current_cred(), "/ro/dir")
+                .map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        assert_eq!(
+            test_vfs_remount("/ro", MountFlag::MS_NOEXEC | MountFlag::MS_NOSUID),
+            Ok(())
+        );
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_open_exec(&current_context(), &current_cred(), "/ro/file")
+                .map(|_| ()),
+            Err(Error::new(EACCES))
+        );
+        let file = test_vfs_open("/ro/file", FileMode::O_RDONLY).unwrap();
+        assert_eq!(
+            REGISTERED_FS
+                .lock()
+                .vfs_exec_cred(&file, &Credentials::new(1000, 1000))
+                .map(|cred| (cred.uid, cred.gid)),
+            Ok((1000, 1000))
+        );
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        /* flags given to vfs_mount */
+        assert_eq!(test_vfs_mount("/ro/dir", MountFlag::MS_RDONLY, ""), Ok(()));
+        assert_eq!(test_vfs_create("/ro/dir/new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_create("/ro/dir_new"), Ok(()));
+        assert_eq!(test_vfs_umount("/ro/dir", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_umount("/ro", UmountFlag::empty()), Ok(()));
+        /* the root filesystem can be flipped to read only as well */
+        assert_eq!(test_vfs_remount("/", MountFlag::MS_RDONLY), Ok(()));
+        assert_eq!(test_vfs_create("/ro_new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_remount("/", MountFlag::empty()), Ok(()));
+
+        // test for bind mounts
+        assert_eq!(test_vfs_mkdir("/bsrc"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/bsrc/sub"), Ok(()));
+        assert_eq!(test_vfs_create("/bsrc/sub/file"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/bsrc/sub/m"), Ok(()));
+        assert_eq!(
+            test_vfs_mount("/bsrc/sub/m", MountFlag::empty(), ""),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/bsrc/sub/m/inner"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/bdst"), Ok(()));
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub", "/bdst", MountFlag::empty()),
+            Err(Error::new(EPERM))
+        );
+        set_cred(Credentials::root());
+        assert_eq!(
+            test_vfs_bind("/bsrc/none", "/bdst", MountFlag::empty()),
+            Err(Error::new(ENOENT))
+        );
+        /* the subtree is shared, but the mounts below it are not carried along */
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub", "/bdst", MountFlag::empty()),
+            Ok(())
+        );
+        assert_eq!(test_vfs_lookup("/bdst/file"), Ok(()));
+        assert_eq!(test_vfs_create("/bdst/new"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bsrc/sub/new"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bdst/m"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bdst/m/inner"), Err(Error::new(ENOENT)));
+        /* `..` leaves through the mountpoint, not through the source */
+        assert_eq!(test_vfs_create("/bdst/../bdst_dotdot"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bdst_dotdot"), Ok(()));
+        assert_eq!(
+            test_vfs_lookup("/bsrc/bdst_dotdot"),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_umount("/bdst", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_lookup("/bdst/file"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_lookup("/bsrc/sub/file"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bsrc/sub/m/inner"), Ok(()));
+        /* a recursive bind mount copies the mounts below the source */
+        assert_eq!(test_vfs_mkdir("/brec"), Ok(()));
+        assert_eq!(test_vfs_bind("/bsrc", "/brec", MountFlag::MS_REC), Ok(()));
+        assert_eq!(test_vfs_lookup("/brec/sub/m/inner"), Ok(()));
+        assert_eq!(test_vfs_create("/brec/sub/m/rec_new"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bsrc/sub/m/rec_new"), Ok(()));
+        assert_eq!(
+            test_vfs_umount("/brec", UmountFlag::empty()),
+            Err(Error::new(EBUSY))
+        );
+        assert_eq!(test_vfs_umount("/brec/sub/m", UmountFlag::empty()), Ok(()));
+        assert_eq!(
+            test_vfs_lookup("/brec/sub/m/inner"),
+            Err(Error::new(ENOENT))
+        );
+        assert_eq!(test_vfs_lookup("/bsrc/sub/m/inner"), Ok(()));
+        assert_eq!(test_vfs_umount("/brec", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_bind("/bsrc", "/brec", MountFlag::MS_REC), Ok(()));
+        assert_eq!(test_vfs_umount("/brec", UmountFlag::MNT_DETACH), Ok(()));
+        assert_eq!(test_vfs_lookup("/brec/sub"), Err(Error::new(ENOENT)));
+        assert_eq!(test_vfs_lookup("/bsrc/sub/m/inner"), Ok(()));
+        /* a read only bind mount of a writable filesystem */
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub", "/bdst", MountFlag::MS_RDONLY),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/bdst/ro_new"), Err(Error::new(EROFS)));
+        assert_eq!(
+            test_vfs_open("/bdst/file", FileMode::O_WRONLY).err(),
+            Some(Error::new(EROFS))
+        );
+        assert_eq!(test_vfs_create("/bsrc/sub/rw_new"), Ok(()));
+        assert_eq!(test_vfs_lookup("/bdst/rw_new"), Ok(()));
+        /* MS_BIND on remount only changes the flags of that mount */
+        let file = test_vfs_open("/bsrc/sub/file", FileMode::O_RDWR).unwrap();
+        assert_eq!(test_vfs_remount("/bdst", MountFlag::MS_BIND), Ok(()));
+        assert_eq!(test_vfs_create("/bdst/ro_new"), Ok(()));
+        assert_eq!(
+            test_vfs_remount("/bdst", MountFlag::MS_BIND | MountFlag::MS_RDONLY),
+            Ok(())
+        );
+        assert_eq!(test_vfs_unlink("/bdst/ro_new"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_write(&file, &data1), Ok(data1.len()));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(test_vfs_umount("/bdst", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_unlink("/bsrc/sub/ro_new"), Ok(()));
+        /* a file can be bound onto a file only */
+        assert_eq!(test_vfs_create("/bfile"), Ok(()));
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub", "/bfile", MountFlag::empty()),
+            Err(Error::new(ENOTDIR))
+        );
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub/file", "/bdst", MountFlag::empty()),
+            Err(Error::new(EISDIR))
+        );
+        assert_eq!(
+            test_vfs_bind("/bsrc/sub/file", "/bfile", MountFlag::empty()),
+            Ok(())
+        );
+        let file = test_vfs_open("/bfile", FileMode::O_RDONLY).unwrap();
+        assert_eq!(test_vfs_read(&file, &mut buf[0..5]), Ok(5));
+        assert_eq!(buf[0..5], data1[..]);
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(test_vfs_unlink("/bfile"), Err(Error::new(EBUSY)));
+        assert_eq!(test_vfs_umount("/bfile", UmountFlag::empty()), Ok(()));
+        assert_eq!(test_vfs_unlink("/bfile"), Ok(()));
+        assert_eq!(test_vfs_umount("/bsrc/sub/m", UmountFlag::empty()), Ok(()));
+
+        // test for mount options
+        assert_eq!(
+            MountOptions::parse(MountFlag::MS_NOEXEC, "ro,,size=16m,exec,uid=1000"),
+            Ok(MountOptions {
+                flags: MountFlag::MS_RDONLY,
+                options: vec![
+                    ("size".to_string(), Some("16m".to_string())),
+                    ("uid".to_string(), Some("1000".to_string())),
+                ],
+            })
+        );
+        assert_eq!(
+            MountOptions::parse(MountFlag::empty(), "=1").map(|_| ()),
+            Err(Error::new(EINVAL))
+        );
+        /* strictatime clears the other access time flags, unless they come later */
+        assert_eq!(
+            MountOptions::parse(MountFlag::empty(), "relatime,noatime,strictatime")
+                .map(|opts| opts.flags),
+            Ok(MountFlag::MS_STRICTATIME)
+        );
+        assert_eq!(
+            MountOptions::parse(MountFlag::empty(), "strictatime,relatime").map(|opts| opts.flags),
+            Ok(MountFlag::MS_RELATIME)
+        );
+        assert_eq!(
+            MountOptions::parse(MountFlag::MS_RELATIME | MountFlag::MS_STRICTATIME, "")
+                .map(|opts| opts.flags),
+            Ok(MountFlag::MS_STRICTATIME)
+        );
+        assert_eq!(memparse("16m"), Ok(16 << 20));
+        assert_eq!(memparse("4K"), Ok(4096));
+        assert_eq!(memparse("12"), Ok(12));
+        assert_eq!(memparse("m"), Err(Error::new(EINVAL)));
+        assert_eq!(test_vfs_mkdir("/opt"), Ok(()));
+        /* unknown options and bad values are rejected, nothing gets mounted */
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "bogus=1"),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "size"),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "mode=0999"),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "uid=-1"),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_mount(
+                &current_context(),
+                &current_cred(),
+                "none",
+                "/opt",
+                FSType::RAMFS,
+                MountFlag::empty(),
+                "ro",
+            ),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/opt/file"), Err(Error::new(EROFS)));
+        assert_eq!(test_vfs_umount("/opt", UmountFlag::empty()), Ok(()));
+        /* the root directory takes the mode and owner from the options */
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "mode=0700,uid=1000,gid=100"),
+            Ok(())
+        );
+        assert_eq!(test_vfs_stat("/opt", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid, stat.gid), (0o700, 1000, 100));
+        set_cred(Credentials::new(1000, 100));
+        assert_eq!(test_vfs_create("/opt/owned"), Ok(()));
+        set_cred(Credentials::new(1001, 100));
+        assert_eq!(test_vfs_create("/opt/other"), Err(Error::new(EACCES)));
+        set_cred(Credentials::root());
+        assert_eq!(test_vfs_umount("/opt", UmountFlag::empty()), Ok(()));
+        /* the limits on the number of inodes and the size */
+        assert_eq!(
+            test_vfs_mount("/opt", MountFlag::empty(), "nr_inodes=3,size=8k"),
+            Ok(())
+        );
+        /* without a mode option the root is open to everyone */
+        assert_eq!(test_vfs_stat("/opt", &mut stat), Ok(()));
+        assert_eq!((stat.perm, stat.uid, stat.gid), (0o755, 0, 0));
+        set_cred(Credentials::new(1000, 1000));
+        assert_eq!(test_vfs_chdir("/opt"), Ok(()));
+        assert_eq!(test_vfs_chdir("/"), Ok(()));
+        set_cred(Credentials::root());
+        let mut statfs = StatFs::default();
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_statfs(
+                &current_context(),
+                &current_cred(),
+                "/opt",
+                &mut statfs
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            (statfs.blocks, statfs.bfree, statfs.files, statfs.ffree),
+            (2, 2, 3, 2)
+        );
+        assert_eq!(test_vfs_create("/opt/a"), Ok(()));
+        assert_eq!(test_vfs_mkdir("/opt/b"), Ok(()));
+        assert_eq!(test_vfs_create("/opt/c"), Err(Error::new(ENOSPC)));
+        let file = test_vfs_open("/opt/a", FileMode::O_RDWR).unwrap();
+        let big = [1u8; 5000];
+        assert_eq!(test_vfs_write(&file, &big), Ok(5000));
+        /* a write which does not fit is cut short */
+        assert_eq!(test_vfs_write(&file, &big), Ok(8192 - 5000));
+        assert_eq!(test_vfs_write(&file, &big), Err(Error::new(ENOSPC)));
+        assert_eq!(test_vfs_pwrite(&file, &data1, 100), Ok(data1.len()));
+        assert_eq!(test_vfs_ftruncate(&file, 8193), Err(Error::new(ENOSPC)));
+        assert_eq!(test_vfs_ftruncate(&file, 4096), Ok(()));
+        /* remount can change the limits, but not below what is used */
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_remount(
+                &current_context(),
+                &current_cred(),
+                "/opt",
+                MountFlag::empty(),
+                "nr_inodes=2",
+            ),
+            Err(Error::new(EINVAL))
+        );
+        assert_eq!(
+            REGISTERED_FS.lock().vfs_remount(
+                &current_context(),
+                &current_cred(),
+                "/opt",
+                MountFlag::empty(),
+                "nr_inodes=0,size=16k",
+            ),
+            Ok(())
+        );
+        assert_eq!(test_vfs_create("/opt/c"), Ok(()));
+        assert_eq!(test_vfs_pwrite(&file, &big, 4096), Ok(5000));
+        assert_eq!(test_vfs_close(&file), Ok(()));
+        drop(file);
+        assert_eq!(test_vfs_umount("/opt", UmountFlag::empty()), Ok(()));
+    }
+
+    fn test_vfs_lookup(path: &str) -> Result<()> {
+        println!(
+            "[vfs_lookup ({})]: {}",
+            path,
+            *REGISTERED_FS
+                .lock()
+                .vfs_lookup(&current_context(), &current_cred(), path)?
+                .read()
+        );
+        Ok(())
+    }
+
+    fn test_vfs_mkdir(path: &str) -> Result<()> {
+        test_vfs_mkdir_mode(path, INodePerm::S_IRWXUGO)
+    }
+    fn test_vfs_mkdir_mode(path: &str, perm: INodePerm) -> Result<()> {
+        println!(
+            "[vfs_mkdir ({})]: {}",
+            path,
+            *REGISTERED_FS
+                .lock()
+                .vfs_mkdir(&current_context(), &current_cred(), path, perm)?
+                .read()
+        );
+        Ok(())
+    }
+    fn test_vfs_unlink(path: &str) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_unlink(&current_context(), &current_cred(), path)?;
+        println!("[vfs_unlink ({})]", path,);
+        Ok(())
+    }
+
+    fn test_vfs_create(path: &str) -> Result<()> {
+        test_vfs_create_mode(path, INodePerm::from_bits_truncate(0o666))
+    }
+    fn test_vfs_create_mode(path: &str, perm: INodePerm) -> Result<()> {
+        println!(
+            "[vfs_create ({})]: {}",
+            path,
+            *REGISTERED_FS
+                .lock()
+                .vfs_create(&current_context(), &current_cred(), path, perm)?
+                .read()
+        );
+        Ok(())
+    }
+    fn test_vfs_open(path: &str, mode: FileMode) -> Result<FileRef> {
+        test_vfs_open_mode(path, mode, INodePerm::from_bits_truncate(0o666))
+    }
+    fn test_vfs_open_mode(path: &str, mode: FileMode, perm: INodePerm) -> Result<FileRef> {
+        let file =
+            REGISTERED_FS
+                .lock()
+                .vfs_open(&current_context(), &current_cred(), path, mode, perm)?;
+        println!("[vfs_open ({})]: {}", path, *file.read());
+        Ok(file)
+    }
+
+    fn test_vfs_mount(target: &str, flags: MountFlag, data: &str) -> Result<()> {
+        REGISTERED_FS.lock().vfs_mount(
+            &current_context(),
+            &current_cred(),
+            "none",
+            target,
+            FSType::RAMFS,
+            flags,
+            data,
+        )?;
+        println!("[vfs_mount ({})]", target);
+        Ok(())
+    }
+
+    fn test_vfs_bind(source: &str, target: &str, flags: MountFlag) -> Result<()> {
+        REGISTERED_FS.lock().vfs_mount(
+            &current_context(),
+            &current_cred(),
+            source,
+            target,
+            FSType::RAMFS,
+            MountFlag::MS_BIND | flags,
+            "",
+        )?;
+        println!("[vfs_mount bind ({} -> {})]", source, target);
+        Ok(())
+    }
+
+    fn test_vfs_umount(target: &str, flags: UmountFlag) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_umount(&current_context(), &current_cred(), target, flags)?;
+        println!("[vfs_umount ({})]", target);
+        Ok(())
+    }
+
+    fn test_vfs_remount(target: &str, flags: MountFlag) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_remount(&current_context(), &current_cred(), target, flags, "")?;
+        println!("[vfs_remount ({}, {:?})]", target, flags);
+        Ok(())
+    }
+
+    fn test_vfs_chdir(path: &str) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_chdir(&current_context(), &current_cred(), path)?;
+        println!("[vfs_chdir ({})]", path);
+        Ok(())
+    }
+
+    fn test_vfs_fchdir(file: &FileRef) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_fchdir(&current_context(), &current_cred(), file)?;
+        println!("[vfs_fchdir ({})]", *file.read());
+        Ok(())
+    }
+
+    fn test_vfs_link(oldpath: &str, newpath: &str) -> Result<()> {
+        println!(
+            "[vfs_link ({} -> {})]: {}",
+            newpath,
+            oldpath,
+            *REGISTERED_FS
+                .lock()
+                .vfs_link(&current_context(), &current_cred(), oldpath, newpath)?
+                .read()
+        );
+        Ok(())
+    }
+
+    fn test_vfs_link_file(file: &FileRef, newpath: &str) -> Result<()> {
+        let dentry = REGISTERED_FS.lock().vfs_link_file(
+            &current_context(),
+            &current_cred(),
+            file,
+            newpath,
+        )?;
+        println!(
+            "[vfs_link_file ({} -> {})]: {}",
+            newpath,
+            *file.read(),
+            *dentry.read()
+        );
+        Ok(())
+    }
+
+    fn test_vfs_tmpfile(path: &str, mode: FileMode, perm: INodePerm) -> Result<FileRef> {
+        let file = REGISTERED_FS.lock().vfs_tmpfile(
+            &current_context(),
+            &current_cred(),
+            path,
+            mode,
+            perm,
+        )?;
+        println!("[vfs_tmpfile ({})]: {}", path, *file.read());
+        Ok(file)
+    }
+
+    fn test_vfs_rename(oldpath: &str, newpath: &str, flags: RenameFlag) -> Result<()> {
+        REGISTERED_FS.lock().vfs_rename(
+            &current_context(),
+            &current_cred(),
+            oldpath,
+            newpath,
+            flags,
+        )?;
+        println!("[vfs_rename ({} -> {} {:?})]", oldpath, newpath, flags);
+        Ok(())
+    }
+
+    fn test_vfs_chmod(path: &str, perm: INodePerm) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_chmod(&current_context(), &current_cred(), path, perm)?;
+        println!("[vfs_chmod ({} {:?})]", path, perm);
+        Ok(())
+    }
+
+    fn test_vfs_chown(path: &str, uid: Option<usize>, gid: Option<usize>) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_chown(&current_context(), &current_cred(), path, uid, gid)?;
+        println!("[vfs_chown ({} {:?} {:?})]", path, uid, gid);
+        Ok(())
+    }
+
+    fn test_vfs_utimensat(path: &str, atime: UTime, mtime: UTime) -> Result<()> {
+        REGISTERED_FS.lock().vfs_utimensat(
+            &current_context(),
+            &current_cred(),
+            path,
+            atime,
+            mtime,
+        )?;
+        println!("[vfs_utimensat ({} {:?} {:?})]", path, atime, mtime);
+        Ok(())
+    }
+
+    fn test_vfs_truncate(path: &str, size: usize) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_truncate(&current_context(), &current_cred(), path, size)?;
+        println!("[vfs_truncate ({} {})]", path, size);
+        Ok(())
+    }
+
+    fn test_vfs_ftruncate(file: &FileRef, size: usize) -> Result<()> {
+        REGISTERED_FS.lock().vfs_ftruncate(file, size)?;
+        println!("[vfs_ftruncate ({} {})]", *file.read(), size);
+        Ok(())
+    }
+
+    fn test_vfs_symlink(target: &str, path: &str) -> Result<()> {
+        println!(
+            "[vfs_symlink ({} -> {})]: {}",
+            path,
+            target,
+            *REGISTERED_FS
+                .lock()
+                .vfs_symlink(&current_context(), &current_cred(), target, path)?
+                .read()
+        );
+        Ok(())
+    }
+
+    fn test_vfs_readlink(path: &str, buf: &mut [u8]) -> Result<usize> {
+        let ret =
+            REGISTERED_FS
+                .lock()
+                .vfs_readlink(&current_context(), &current_cred(), path, buf)?;
+        println!("[vfs_readlink ({} {:?})] ret: {}", path, buf, ret);
+        Ok(ret)
+    }
+
+    fn test_vfs_close(file: &FileRef) -> Result<()> {
+        REGISTERED_FS.lock().vfs_close(file)?;
+        println!("[vfs_close ({})]", *file.read());
+        Ok(())
+    }
+
+    fn test_vfs_write(file: &FileRef, data: &[u8]) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_write(file, data)?;
+        println!("[vfs_write ({} {:?})] ret: {}", *file.read(), data, ret);
+        Ok(ret)
+    }
+
+    fn test_vfs_read(file: &FileRef, data: &mut [u8]) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_read(file, data)?;
+        println!("[vfs_read ({} {:?})] ret: {}", *file.read(), data, ret);
+        Ok(ret)
+    }
+
+    fn test_vfs_pwrite(file: &FileRef, data: &[u8], offset: usize) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_pwrite(file, data, offset)?;
+        println!("[vfs_pwrite ({} {})] ret: {}", *file.read(), offset, ret);
+        Ok(ret)
+    }
+    fn test_vfs_pread(file: &FileRef, data: &mut [u8], offset: usize) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_pread(file, data, offset)?;
+        println!("[vfs_pread ({} {})] ret: {}", *file.read(), offset, ret);
+        Ok(ret)
+    }
+    fn test_vfs_writev(file: &FileRef, iov: &[&[u8]]) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_writev(file, iov)?;
+        println!("[vfs_writev ({})] ret: {}", *file.read(), ret);
+        Ok(ret)
+    }
+    fn test_vfs_readv(file: &FileRef, iov: &mut [&mut [u8]]) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_readv(file, iov)?;
+        println!("[vfs_readv ({})] ret: {}", *file.read(), ret);
+        Ok(ret)
+    }
+    fn test_vfs_lseek(file: &FileRef, offset: isize, whence: Whence) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_lseek(file, offset, whence)?;
+        println!(
+            "[vfs_lseek ({} {} {:?})] ret: {}",
+            *file.read(),
+            offset,
+            whence,
+            ret
+        );
+        Ok(ret)
+    }
+    fn test_vfs_readdir(file: &FileRef, dirs: &mut [Direntory]) -> Result<usize> {
+        let ret = REGISTERED_FS.lock().vfs_readdir(file, dirs)?;
+        println!("[vfs_readdir ({} {:?})] ret: {}", *file.read(), dirs, ret);
+        Ok(ret)
+    }
+    fn test_vfs_stat(path: &str, stat: &mut Stat) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_stat(&current_context(), &current_cred(), path, stat)?;
+        println!("[vfs_stat ({} {:?})]", path, stat);
+        Ok(())
+    }
+
+    fn test_vfs_fstat(file: &FileRef, stat: &mut Stat) -> Result<()> {
+        REGISTERED_FS.lock().vfs_fstat(file, stat)?;
+        println!("[vfs_fstat ({} {:?})]", *file.read(), stat);
+        Ok(())
+    }
+
+    fn test_vfs_lstat(path: &str, stat: &mut Stat) -> Result<()> {
+        REGISTERED_FS
+            .lock()
+            .vfs_lstat(&current_context(), &current_cred(), path, stat)?;
+        println!("[vfs_lstat ({} {:?})]", path, stat);
         Ok(())
     }
 }
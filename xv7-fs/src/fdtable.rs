@@ -0,0 +1,190 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use derive_new::new;
+use spin::RwLock;
+use usyscall::error::*;
+use usyscall::fs::*;
+
+use crate::vfs::{FileRef, RegisteredFS};
+
+pub type FdTableRef = Arc<RwLock<FdTable>>;
+
+/* max number of file descriptors of a task */
+pub const NR_OPEN: usize = 1024;
+
+bitflags! {
+pub struct FdFlag:u32 {
+    const FD_CLOEXEC = 0b00000001;  // close the descriptor on exec
+}
+}
+
+impl Default for FdFlag {
+    fn default() -> Self {
+        FdFlag::empty()
+    }
+}
+
+#[derive(Clone)]
+struct FdEntry {
+    file: FileRef,
+    flags: FdFlag,
+}
+
+/* file descriptors of a task, each slot refers to an open file description
+ * which may be shared with other slots or tasks, see `struct files_struct` in linux.
+ * `File::ref_count` counts the slots referring to a description, the description
+ * is closed when the last of them goes away */
+#[derive(new, Default)]
+pub struct FdTable {
+    #[new(default)]
+    entries: Vec<Option<FdEntry>>,
+}
+
+impl FdTable {
+    /* install `file` at the lowest free descriptor, FD_CLOEXEC is set if the
+     * file was opened with O_CLOEXEC */
+    pub fn install(&mut self, file: FileRef) -> Result<usize> {
+        let flags = if file.read().mode.contains(FileMode::O_CLOEXEC) {
+            FdFlag::FD_CLOEXEC
+        } else {
+            FdFlag::empty()
+        };
+        let fd = self.alloc_fd()?;
+        self.set_entry(fd, file, flags);
+        Ok(fd)
+    }
+    pub fn get(&self, fd: usize) -> Result<FileRef> {
+        self.entry(fd).map(|entry| entry.file.clone())
+    }
+    pub fn get_flags(&self, fd: usize) -> Result<FdFlag> {
+        self.entry(fd).map(|entry| entry.flags)
+    }
+    pub fn set_flags(&mut self, fd: usize, flags: FdFlag) -> Result<()> {
+        self.entry(fd)?;
+        self.entries[fd].as_mut().unwrap().flags = flags;
+        Ok(())
+    }
+    pub fn close(&mut self, fs: &mut RegisteredFS, fd: usize) -> Result<()> {
+        self.entry(fd)?;
+        let entry = self.entries[fd].take().unwrap();
+        put_file(fs, &entry.file)
+    }
+    /* the new descriptor shares the open file description, FD_CLOEXEC is not copied */
+    pub fn dup(&mut self, oldfd: usize) -> Result<usize> {
+        let file = self.get(oldfd)?;
+        let fd = self.alloc_fd()?;
+        self.set_entry(fd, file, FdFlag::empty());
+        Ok(fd)
+    }
+    /* same as `dup`, but uses `newfd` and silently closes it first if it is in use */
+    pub fn dup2(&mut self, fs: &mut RegisteredFS, oldfd: usize, newfd: usize) -> Result<usize> {
+        if oldfd == newfd {
+            self.entry(oldfd)?;
+            return Ok(newfd);
+        }
+        self.dup3(fs, oldfd, newfd, FdFlag::empty())
+    }
+    /* same as `dup2`, but `oldfd` and `newfd` must differ and the flags of
+     * `newfd` are given by `flags` */
+    pub fn dup3(
+        &mut self,
+        fs: &mut RegisteredFS,
+        oldfd: usize,
+        newfd: usize,
+        flags: FdFlag,
+    ) -> Result<usize> {
+        if oldfd == newfd || !(flags - FdFlag::FD_CLOEXEC).is_empty() {
+            return Err(Error::new(EINVAL));
+        }
+        let file = self.get(oldfd)?;
+        if newfd >= NR_OPEN {
+            return Err(Error::new(EBADF));
+        }
+        if newfd >= self.entries.len() {
+            self.entries.resize(newfd + 1, None);
+        }
+        if let Some(entry) = self.entries[newfd].take() {
+            /* errors of the implicit close are ignored, as dup2 does */
+            let _ = put_file(fs, &entry.file);
+        }
+        self.set_entry(newfd, file, flags);
+        Ok(newfd)
+    }
+    /* the table of a forked task, both tables share the open file descriptions */
+    pub fn fork(&self) -> FdTable {
+        for entry in self.entries.iter().flatten() {
+            entry.file.write().ref_count += 1;
+        }
+        FdTable {
+            entries: self.entries.clone(),
+        }
+    }
+    /* close the descriptors with FD_CLOEXEC, called when the task execs */
+    pub fn close_on_exec(&mut self, fs: &mut RegisteredFS) -> Result<()> {
+        let mut result = Ok(());
+        for fd in 0..self.entries.len() {
+            let cloexec = match &self.entries[fd] {
+                Some(entry) => entry.flags.contains(FdFlag::FD_CLOEXEC),
+                None => false,
+            };
+            if cloexec {
+                /* an error does not keep the remaining descriptors open */
+                let ret = self.close(fs, fd);
+                if result.is_ok() {
+                    result = ret;
+                }
+            }
+        }
+        result
+    }
+    /* close every descriptor, called when the task exits */
+    pub fn close_all(&mut self, fs: &mut RegisteredFS) -> Result<()> {
+        let mut result = Ok(());
+        for fd in 0..self.entries.len() {
+            if self.entries[fd].is_some() {
+                let ret = self.close(fs, fd);
+                if result.is_ok() {
+                    result = ret;
+                }
+            }
+        }
+        self.entries.clear();
+        result
+    }
+
+    fn entry(&self, fd: usize) -> Result<&FdEntry> {
+        match self.entries.get(fd) {
+            Some(Some(entry)) => Ok(entry),
+            _ => Err(Error::new(EBADF)),
+        }
+    }
+    fn alloc_fd(&mut self) -> Result<usize> {
+        match self.entries.iter().position(|entry| entry.is_none()) {
+            Some(fd) => Ok(fd),
+            None if self.entries.len() < NR_OPEN => {
+                self.entries.push(None);
+                Ok(self.entries.len() - 1)
+            }
+            None => Err(Error::new(EMFILE)),
+        }
+    }
+    fn set_entry(&mut self, fd: usize, file: FileRef, flags: FdFlag) {
+        file.write().ref_count += 1;
+        self.entries[fd] = Some(FdEntry { file, flags });
+    }
+}
+
+/* drop a reference to an open file description, and close it with the last one,
+ * the file is flushed either way */
+fn put_file(fs: &mut RegisteredFS, file: &FileRef) -> Result<()> {
+    let ref_count = {
+        let mut fw = file.write();
+        fw.ref_count -= 1;
+        fw.ref_count
+    };
+    if ref_count == 0 {
+        fs.vfs_close(file)
+    } else {
+        fs.vfs_flush(file)
+    }
+}
@@ -5,4 +5,5 @@
 extern crate bitflags;
 extern crate alloc;
 
+pub mod fdtable;
 pub mod vfs;
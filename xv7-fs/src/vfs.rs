@@ -1,4 +1,6 @@
+use alloc::boxed::Box;
 use alloc::string::String;
+use core::any::Any;
 use core::fmt::Debug;
 use core::ptr;
 use core::str;
@@ -7,6 +9,7 @@ use derive_more::Display;
 use crate::alloc::string::ToString;
 use alloc::collections::btree_map::BTreeMap;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt;
 use derive_new::new;
@@ -15,7 +18,7 @@ use usyscall::error::*;
 use usyscall::fs::*;
 use Option::*;
 
-pub type FSMountFunc = fn(&str) -> (FSRef, DentryRef);
+pub type FSMountFunc = fn(&str, &MountOptions) -> Result<(FSRef, DentryRef)>;
 pub type FSRef = Arc<dyn FileSystem>;
 pub type FSWeakRef = Weak<dyn FileSystem>;
 
@@ -26,6 +29,23 @@ pub type INodeRef = Arc<dyn INode>;
 pub type INodeWeakRef = Weak<dyn INode>;
 
 pub type FileRef = Arc<RwLock<File>>;
+pub type FileOperationsRef = Arc<dyn FileOperations>;
+
+pub type FSContextRef = Arc<RwLock<FSContext>>;
+
+pub type ClockRef = Arc<dyn Clock>;
+
+pub type MountRef = Arc<RwLock<Mount>>;
+pub type MountWeakRef = Weak<RwLock<Mount>>;
+
+/* max number of symbolic links followed in a single lookup */
+const MAXSYMLINKS: usize = 40;
+
+/* with MS_RELATIME an access time older than this is updated anyway */
+const RELATIME_INTERVAL: usize = 24 * 60 * 60;
+
+/* offsets 0 and 1 of a directory are `.` and `..`, which are filled by the vfs */
+pub const DIR_DOT_ENTRIES: usize = 2;
 
 #[derive(Debug, Display, PartialEq, Clone, Eq, Ord, PartialOrd, Copy)]
 pub enum FSType {
@@ -36,7 +56,11 @@ pub enum FSType {
 pub struct RegisteredFS {
     mount_infos: BTreeMap<FSType, (FSMountFunc, Vec<FSRef>)>,
     root_dentry: Option<DentryRef>,
+    root_mnt: Option<MountRef>,
+    /* every attached mount, including the root one */
+    mounts: Vec<MountRef>,
     opened_files: Vec<FileRef>,
+    clock: Option<ClockRef>,
 }
 
 #[allow(unused_must_use)]
@@ -62,27 +86,394 @@ impl RegisteredFS {
         self.mount_infos
             .insert(fstype, (fs_mount, Default::default()));
     }
-    pub fn mount_fs(&mut self, fstype: FSType, dev_name: &str) -> (FSRef, DentryRef) {
-        if let Some((mount, mounted_fss)) = self.mount_infos.get_mut(&fstype) {
-            // fake mount
-            let result = mount(dev_name);
-            mounted_fss.push(result.0.clone());
-            result
-        } else {
-            panic!("filesystem not found: {}", fstype);
-        }
+    pub fn mount_fs(
+        &mut self,
+        fstype: FSType,
+        dev_name: &str,
+        opts: &MountOptions,
+    ) -> Result<(FSRef, DentryRef)> {
+        let (mount, mounted_fss) = self
+            .mount_infos
+            .get_mut(&fstype)
+            .ok_or_else(|| Error::new(ENODEV))?;
+        // fake mount
+        let result = mount(dev_name, opts)?;
+        mounted_fss.push(result.0.clone());
+        Ok(result)
     }
     pub fn set_root(&mut self, dentry: &DentryRef) {
+        let fs = dentry.read().get_inode().unwrap().get_fs();
+        let mnt = Arc::new(RwLock::new(Mount::new(fs, dentry.clone())));
+        self.mounts.push(mnt.clone());
+        self.root_mnt = Some(mnt);
         self.root_dentry = Some(dentry.clone());
     }
+    /* mount a new filesystem of `fstype` on the directory `target`, hiding its
+     * content until it is unmounted, `data` holds options such as `ro,size=16m`.
+     * MS_BIND attaches the subtree at `source` instead.
+     * MS_REC also attaches the mounts below it. */
+    #[allow(clippy::too_many_arguments)]
+    pub fn vfs_mount(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        source: &str,
+        target: &str,
+        fstype: FSType,
+        flags: MountFlag,
+        data: &str,
+    ) -> Result<()> {
+        if !cred.is_root() {
+            return Err(Error::new(EPERM));
+        }
+        if flags.contains(MountFlag::MS_BIND) {
+            return self.do_loopback(ctx, cred, source, target, flags);
+        }
+        if !self.mount_infos.contains_key(&fstype) {
+            return Err(Error::new(ENODEV));
+        }
+        let nd = self.path_lookup(
+            ctx,
+            cred,
+            target,
+            LookupFlag::LOOKUP_FOLLOW | LookupFlag::LOOKUP_DIRECTORY,
+        )?;
+        let mut opts = MountOptions::parse(flags, data)?;
+        let mnt_flags = opts.flags - MountFlag::MS_SYNCHRONOUS;
+        /* the filesystem only sees the flags of the superblock */
+        opts.flags = sb_flags(opts.flags);
+        let (fs, root) = self.mount_fs(fstype, source, &opts)?;
+        let mut mnt = Mount::new(fs, root);
+        mnt.parent = Arc::downgrade(&nd.mnt);
+        mnt.mountpoint = Some(nd.current);
+        mnt.flags = mnt_flags;
+        self.mounts.push(Arc::new(RwLock::new(mnt)));
+        Ok(())
+    }
+    /* attach the subtree at `source` to `target` as well, with MS_REC the
+     * mounts below `source` are copied along, otherwise they stay hidden */
+    fn do_loopback(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        source: &str,
+        target: &str,
+        flags: MountFlag,
+    ) -> Result<()> {
+        let (old_current, old_mnt) = {
+            let old = self.path_lookup(ctx, cred, source, LookupFlag::LOOKUP_FOLLOW)?;
+            (old.current, old.mnt)
+        };
+        let (current, mnt) = {
+            let nd = self.path_lookup(ctx, cred, target, LookupFlag::LOOKUP_FOLLOW)?;
+            (nd.current, nd.mnt)
+        };
+        if old_mnt.read().umounted {
+            return Err(Error::new(EINVAL));
+        }
+        /* a directory can only cover a directory, and a file only a file */
+        let old_is_dir = old_current.read().get_inode()?.get_metadata()?.mode == INodeType::IFDIR;
+        let is_dir = current.read().get_inode()?.get_metadata()?.mode == INodeType::IFDIR;
+        if old_is_dir && !is_dir {
+            return Err(Error::new(ENOTDIR));
+        }
+        if !old_is_dir && is_dir {
+            return Err(Error::new(EISDIR));
+        }
+        let mnt_flags =
+            flags - (MountFlag::MS_BIND | MountFlag::MS_REC | MountFlag::MS_SYNCHRONOUS);
+        let mut copies = vec![(old_mnt.clone(), self.clone_mnt(&old_mnt, &old_current))];
+        if flags.contains(MountFlag::MS_REC) {
+            /* the copies are only attached at the end, so binding a tree
+             * into itself does not walk into the new mounts */
+            let mut i = 0;
+            while i < copies.len() {
+                let (src, dst) = copies[i].clone();
+                let dst_root = dst.read().root.clone();
+                for child in &self.mounts {
+                    let child_parent = child.read().parent.upgrade();
+                    if !child_parent.is_some_and(|p| Arc::ptr_eq(&p, &src)) {
+                        continue;
+                    }
+                    let mountpoint = match child.read().mountpoint.clone() {
+                        Some(mountpoint) => mountpoint,
+                        None => continue,
+                    };
+                    if !is_ancestor(&dst_root, &mountpoint) {
+                        continue;
+                    }
+                    let child_root = child.read().root.clone();
+                    let copy = self.clone_mnt(child, &child_root);
+                    {
+                        let mut copyw = copy.write();
+                        copyw.parent = Arc::downgrade(&dst);
+                        copyw.mountpoint = Some(mountpoint);
+                    }
+                    copies.push((child.clone(), copy));
+                }
+                i += 1;
+            }
+        }
+        {
+            let mut topw = copies[0].1.write();
+            topw.parent = Arc::downgrade(&mnt);
+            topw.mountpoint = Some(current);
+            topw.flags = mnt_flags;
+        }
+        self.mounts.extend(copies.into_iter().map(|(_, copy)| copy));
+        Ok(())
+    }
+    /* a new mount of the filesystem of `mnt` rooted at `root`, not attached anywhere yet */
+    fn clone_mnt(&self, mnt: &MountRef, root: &DentryRef) -> MountRef {
+        let mnt = mnt.read();
+        let mut copy = Mount::new(mnt.fs.clone(), root.clone());
+        copy.flags = mnt.flags;
+        copy.sb_mounts = mnt.sb_mounts.clone();
+        Arc::new(RwLock::new(copy))
+    }
+    /* remove the filesystem mounted on `target` from the namespace, it fails
+     * with EBUSY while the mount is in use by open files, the cwd or root of a
+     * task, or other mounts on top of it, unless MNT_DETACH is given, then the
+     * mount and everything mounted below it is detached at once and torn down
+     * when its last user goes away */
+    pub fn vfs_umount(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        target: &str,
+        flags: UmountFlag,
+    ) -> Result<()> {
+        if !cred.is_root() {
+            return Err(Error::new(EPERM));
+        }
+        let lookup_flags = if flags.contains(UmountFlag::UMOUNT_NOFOLLOW) {
+            LookupFlag::empty()
+        } else {
+            LookupFlag::LOOKUP_FOLLOW
+        };
+        let (current, mnt) = {
+            let nd = self.path_lookup(ctx, cred, target, lookup_flags)?;
+            (nd.current, nd.mnt)
+        };
+        if !Arc::ptr_eq(&current, &mnt.read().root) {
+            return Err(Error::new(EINVAL));
+        }
+        /* the root of the whole tree stays */
+        if mnt.read().parent.upgrade().is_none() {
+            return Err(Error::new(EBUSY));
+        }
+        let umounts = if flags.contains(UmountFlag::MNT_DETACH) {
+            self.mount_tree(&mnt)
+        } else {
+            /* a user of the mount holds a `MountRef` to it: an open file, the root or cwd
+             * of a task, or a lookup in progress. Only the mount table and `mnt` are
+             * expected here. A dentry does not refer to its mount and does not count */
+            if Arc::strong_count(&mnt) > 2 || self.mount_tree(&mnt).len() > 1 {
+                return Err(Error::new(EBUSY));
+            }
+            vec![mnt.clone()]
+        };
+        {
+            /* `..` can not lead back into the namespace anymore */
+            let mut mntw = mnt.write();
+            mntw.parent = Weak::new();
+            mntw.mountpoint = None;
+        }
+        self.mounts
+            .retain(|attached| !umounts.iter().any(|umount| Arc::ptr_eq(attached, umount)));
+        for umount in &umounts {
+            let fs = {
+                let mut umountw = umount.write();
+                umountw.umounted = true;
+                umountw.fs.clone()
+            };
+            /* the filesystem may still be mounted somewhere else */
+            if !self.mounts.iter().any(|m| Arc::ptr_eq(&m.read().fs, &fs)) {
+                for (_, mounted_fss) in self.mount_infos.values_mut() {
+                    mounted_fss.retain(|mounted| !Arc::ptr_eq(mounted, &fs));
+                }
+            }
+        }
+        Ok(())
+    }
+    /* change the flags and the options of the filesystem mounted on `target`,
+     * it can not become read only while a file on it is open for writing.
+     * MS_BIND changes the flags of that mount only. */
+    pub fn vfs_remount(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        target: &str,
+        flags: MountFlag,
+        data: &str,
+    ) -> Result<()> {
+        if !cred.is_root() {
+            return Err(Error::new(EPERM));
+        }
+        let nd = self.path_lookup(ctx, cred, target, LookupFlag::LOOKUP_FOLLOW)?;
+        if !Arc::ptr_eq(&nd.current, &nd.mnt.read().root) {
+            return Err(Error::new(EINVAL));
+        }
+        let mut opts = MountOptions::parse(flags, data)?;
+        let fs = nd.mnt.read().fs.clone();
+        let bind = opts.flags.contains(MountFlag::MS_BIND);
+        if opts.flags.contains(MountFlag::MS_RDONLY) {
+            for file in &self.opened_files {
+                let fr = file.read();
+                let busy = if bind {
+                    Arc::ptr_eq(&fr.mnt, &nd.mnt)
+                } else {
+                    Arc::ptr_eq(&fr.mnt.read().fs, &fs)
+                };
+                if is_writable(fr.mode) && busy {
+                    return Err(Error::new(EBUSY));
+                }
+            }
+        }
+        let mnt_flags = opts.flags - (MountFlag::MS_BIND | MountFlag::MS_SYNCHRONOUS);
+        if !bind {
+            opts.flags = sb_flags(opts.flags);
+            fs.remount(&opts)?;
+        }
+        nd.mnt.write().flags = mnt_flags;
+        Ok(())
+    }
+    /* `mnt` and every mount attached below it */
+    fn mount_tree(&self, mnt: &MountRef) -> Vec<MountRef> {
+        let mut tree = vec![mnt.clone()];
+        let mut i = 0;
+        while i < tree.len() {
+            for child in &self.mounts {
+                let parent = child.read().parent.upgrade();
+                if parent.is_some_and(|parent| Arc::ptr_eq(&parent, &tree[i])) {
+                    tree.push(child.clone());
+                }
+            }
+            i += 1;
+        }
+        tree
+    }
+    /* the mount attached on `dentry` of `mnt`, the last one if several are stacked */
+    fn lookup_mnt(&self, mnt: &MountRef, dentry: &DentryRef) -> Option<MountRef> {
+        self.mounts
+            .iter()
+            .rev()
+            .find(|child| {
+                let child = child.read();
+                let on_dentry = match &child.mountpoint {
+                    Some(mountpoint) => Arc::ptr_eq(mountpoint, dentry),
+                    None => false,
+                };
+                on_dentry && child.parent.upgrade().is_some_and(|p| Arc::ptr_eq(&p, mnt))
+            })
+            .cloned()
+    }
+    fn is_mountpoint(&self, dentry: &DentryRef) -> bool {
+        self.mounts.iter().any(|mnt| match &mnt.read().mountpoint {
+            Some(mountpoint) => Arc::ptr_eq(mountpoint, dentry),
+            None => false,
+        })
+    }
     pub fn get_root(&mut self) -> DentryRef {
         if self.root_dentry.is_none() {
             panic!("rootfs was not set!")
         }
         self.root_dentry.as_ref().unwrap().clone()
     }
-    fn path_lookup<'a>(&mut self, path: &'a str, flags: LookupFlag) -> Result<NameIData<'a>> {
-        let mut nd = self.path_init(path, flags);
+    /* the clock used to stamp inodes, all timestamps are 0 until it is set */
+    pub fn set_clock(&mut self, clock: ClockRef) {
+        self.clock = Some(clock);
+    }
+    fn current_time(&self) -> usize {
+        self.clock.as_ref().map_or(0, |clock| clock.now())
+    }
+    fn touch(&self, inode: &INodeRef, flags: TimeFlag) -> Result<()> {
+        inode.update_time(self.current_time(), flags)
+    }
+    /* update the access time after `file` is read, as the mount allows */
+    fn file_accessed(&self, file: &FileRef) -> Result<()> {
+        let (mnt, inode) = {
+            let fr = file.read();
+            (fr.mnt.clone(), fr.inode.clone())
+        };
+        let mnt = mnt.read();
+        if mnt.is_readonly() || mnt.flags.contains(MountFlag::MS_NOATIME) {
+            return Ok(());
+        }
+        let md = inode.get_metadata()?;
+        if md.mode == INodeType::IFDIR && mnt.flags.contains(MountFlag::MS_NODIRATIME) {
+            return Ok(());
+        }
+        let now = self.current_time();
+        if mnt.flags.contains(MountFlag::MS_RELATIME)
+            && md.atime > md.mtime
+            && md.atime > md.ctime
+            && now.saturating_sub(md.atime) < RELATIME_INTERVAL
+        {
+            return Ok(());
+        }
+        self.touch(&inode, TimeFlag::S_ATIME)
+    }
+    /* `dentry` was just created in the directory `dir` */
+    fn touch_created(&self, dir: &INodeRef, dentry: &DentryRef) -> Result<()> {
+        self.touch(&dentry.read().get_inode()?, TimeFlag::all())?;
+        self.touch(dir, TimeFlag::S_MTIME | TimeFlag::S_CTIME)
+    }
+    /* free an inode once it has neither a name nor an opened file, see `iput` in linux */
+    fn iput(&self, inode: &INodeRef) -> Result<()> {
+        if inode.get_metadata()?.nlink != 0 {
+            return Ok(());
+        }
+        for file in &self.opened_files {
+            if ptr::eq(inode.as_ref(), file.read().inode.as_ref()) {
+                return Ok(());
+            }
+        }
+        inode.get_fs().evict_inode(inode)
+    }
+    /* every change of attributes updates ctime, see `notify_change` in linux */
+    fn notify_change(&self, dentry: &DentryRef, inode: &INodeRef, attr: &INodeAttr) -> Result<()> {
+        let mut attr = attr.clone();
+        attr.valid |= AttrMask::ATTR_CTIME;
+        attr.ctime = self.current_time();
+        inode.setattr(dentry, &attr)
+    }
+    /* create a context for a new task, both root and cwd point to the global root */
+    pub fn new_context(&mut self) -> FSContextRef {
+        let root = self.get_root();
+        let root_mnt = self.root_mnt.as_ref().unwrap().clone();
+        Arc::new(RwLock::new(FSContext::new(
+            root.clone(),
+            root,
+            root_mnt.clone(),
+            root_mnt,
+        )))
+    }
+    /* the child task starts with a copy of its parent's root and cwd */
+    pub fn fork_context(&mut self, ctx: &FSContextRef) -> FSContextRef {
+        Arc::new(RwLock::new(ctx.read().clone()))
+    }
+    /* set the umask of a task and return the previous one */
+    pub fn vfs_umask(&mut self, ctx: &FSContextRef, mask: INodePerm) -> INodePerm {
+        let mut ctx = ctx.write();
+        let old = ctx.umask;
+        ctx.umask = mask & INodePerm::S_IRWXUGO;
+        old
+    }
+    fn path_lookup(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        flags: LookupFlag,
+    ) -> Result<NameIData> {
+        /* a trailing slash means the last component must be a directory */
+        let flags = if path.ends_with('/') {
+            flags | LookupFlag::LOOKUP_FOLLOW | LookupFlag::LOOKUP_DIRECTORY
+        } else {
+            flags
+        };
+        let mut nd = self.path_init(ctx, cred, path, flags)?;
         self.path_walk(&mut nd, flags)?;
         if nd.cur_ind < nd.paths.len() {
             // `path` may be '/'
@@ -93,25 +484,39 @@ impl RegisteredFS {
         Ok(nd)
     }
 
-    fn path_init<'a>(&mut self, path: &'a str, _flags: LookupFlag) -> NameIData<'a> {
-        if path.starts_with('/') {
-            let root = self.get_root();
-            let current = root.clone();
-            NameIData {
-                current: current,
-                root: root,
-                paths: path.split('/').filter(|s| *s != "").collect(),
-                cur_ind: 0,
-            }
-        } else {
-            todo!();
+    fn path_init(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        _flags: LookupFlag,
+    ) -> Result<NameIData> {
+        if path.is_empty() {
+            return Err(Error::new(ENOENT));
         }
+        let ctx = ctx.read();
+        /* absolute path starts from the root of the task, others from its cwd */
+        let (current, mnt) = if path.starts_with('/') {
+            (ctx.root.clone(), ctx.root_mnt.clone())
+        } else {
+            (ctx.cwd.clone(), ctx.cwd_mnt.clone())
+        };
+        Ok(NameIData {
+            current,
+            mnt,
+            root: ctx.root.clone(),
+            root_mnt: ctx.root_mnt.clone(),
+            cred: cred.clone(),
+            paths: split_path(path),
+            cur_ind: 0,
+            link_count: 0,
+        })
     }
 
     fn path_walk(&mut self, nd: &mut NameIData, flags: LookupFlag) -> Result<()> {
         let cur_inode = nd.current.read().get_inode()?;
 
-        if cur_inode.get_metadata().mode != INodeType::IFDIR {
+        if cur_inode.get_metadata()?.mode != INodeType::IFDIR {
             return Err(Error::new(ENOTDIR));
         }
         while nd.cur_ind + 1 < nd.paths.len() {
@@ -121,35 +526,134 @@ impl RegisteredFS {
     }
 
     fn lookup_last(&mut self, nd: &mut NameIData, flags: LookupFlag) -> Result<()> {
-        let dentry = self.lookup_at(nd.paths[nd.cur_ind], &nd.current, flags)?;
-        if flags.contains(LookupFlag::LOOKUP_DIRECTORY) {
-            match dentry.read().inode.upgrade() {
-                Some(inode) => {
-                    if inode.get_metadata().mode != INodeType::IFDIR {
-                        return Err(Error::new(ENOTDIR));
-                    }
-                    Ok(())
-                }
-                None => Err(Error::new(ENOENT)),
-            }?;
+        may_lookup(nd)?;
+        /* `.` and `..` always lead to a directory */
+        if self.walk_dots(nd) {
+            return Ok(());
+        }
+        let dentry = self.lookup_at(&nd.paths[nd.cur_ind], &nd.current, flags)?;
+        let inode = dentry.read().get_inode()?;
+        let mode = inode.get_metadata()?.mode;
+        if mode == INodeType::IFLNK && flags.contains(LookupFlag::LOOKUP_FOLLOW) {
+            self.follow_link(nd, &dentry, &inode)?;
+            /* the link may point to another path with several components */
+            self.path_walk(nd, flags)?;
+            if nd.cur_ind < nd.paths.len() {
+                return self.lookup_last(nd, flags);
+            }
+            return Ok(());
+        }
+        if flags.contains(LookupFlag::LOOKUP_DIRECTORY) && mode != INodeType::IFDIR {
+            return Err(Error::new(ENOTDIR));
         }
         nd.cur_ind += 1;
         nd.current = dentry.clone();
+        if !flags.contains(LookupFlag::LOOKUP_NOMOUNT) {
+            self.follow_mount(nd);
+        }
         return Ok(());
     }
 
     fn walk_component(&mut self, nd: &mut NameIData, flags: LookupFlag) -> Result<()> {
-        let dentry = self.lookup_at(nd.paths[nd.cur_ind], &nd.current, flags)?;
-        let nexti = dentry.read().inode.upgrade();
-        if nexti.is_none() {
+        may_lookup(nd)?;
+        if self.walk_dots(nd) {
+            return Ok(());
+        }
+        let dentry = self.lookup_at(&nd.paths[nd.cur_ind], &nd.current, flags)?;
+        let nexti = dentry.read().get_inode()?;
+        match nexti.get_metadata()?.mode {
+            INodeType::IFDIR => {
+                nd.cur_ind += 1;
+                nd.current = dentry.clone();
+                self.follow_mount(nd);
+                Ok(())
+            }
+            /* links in the middle of a path are always followed */
+            INodeType::IFLNK => self.follow_link(nd, &dentry, &nexti),
+            _ => Err(Error::new(ENOTDIR)),
+        }
+    }
+
+    /* enter the roots of the filesystems mounted on the current dentry */
+    fn follow_mount(&self, nd: &mut NameIData) {
+        while let Some(child) = self.lookup_mnt(&nd.mnt, &nd.current) {
+            nd.current = child.read().root.clone();
+            nd.mnt = child;
+        }
+    }
+
+    /* replace the current component with the components of the link target,
+     * so that the walk continues from the target */
+    fn follow_link(
+        &mut self,
+        nd: &mut NameIData,
+        dentry: &DentryRef,
+        inode: &INodeRef,
+    ) -> Result<()> {
+        nd.link_count += 1;
+        if nd.link_count > MAXSYMLINKS {
+            return Err(Error::new(ELOOP));
+        }
+        let target = inode.readlink(dentry)?;
+        if target.is_empty() {
             return Err(Error::new(ENOENT));
         }
-        if nexti.unwrap().get_metadata().mode != INodeType::IFDIR {
-            Err(Error::new(ENOTDIR))
-        } else {
-            nd.cur_ind += 1;
-            nd.current = dentry.clone();
-            Ok(())
+        if target.starts_with('/') {
+            nd.current = nd.root.clone();
+            nd.mnt = nd.root_mnt.clone();
+        }
+        let rest = nd.paths.split_off(nd.cur_ind + 1);
+        nd.paths.truncate(nd.cur_ind);
+        nd.paths.extend(split_path(&target));
+        nd.paths.extend(rest);
+        Ok(())
+    }
+
+    /* `.` and `..` are resolved by the vfs instead of the filesystem */
+    fn walk_dots(&mut self, nd: &mut NameIData) -> bool {
+        match nd.paths[nd.cur_ind].as_str() {
+            "." => {}
+            ".." => {
+                self.follow_dotdot(nd);
+                /* the parent may be covered by another mount */
+                self.follow_mount(nd);
+            }
+            _ => return false,
+        }
+        nd.cur_ind += 1;
+        true
+    }
+
+    /* move to the parent directory, `..` of the root of the task is itself, and
+     * `..` of the root of a mounted filesystem is `..` of the dentry it is mounted on */
+    fn follow_dotdot(&mut self, nd: &mut NameIData) {
+        loop {
+            if Arc::ptr_eq(&nd.current, &nd.root) && Arc::ptr_eq(&nd.mnt, &nd.root_mnt) {
+                break;
+            }
+            let (mnt_root, parent_mnt, mountpoint) = {
+                let mnt = nd.mnt.read();
+                (
+                    mnt.root.clone(),
+                    mnt.parent.upgrade(),
+                    mnt.mountpoint.clone(),
+                )
+            };
+            if !Arc::ptr_eq(&nd.current, &mnt_root) {
+                let parent = nd.current.read().parent.upgrade();
+                if let Some(parent) = parent {
+                    nd.current = parent;
+                }
+                break;
+            }
+            match (parent_mnt, mountpoint) {
+                (Some(parent_mnt), Some(mountpoint)) => {
+                    nd.mnt = parent_mnt;
+                    nd.current = mountpoint;
+                }
+                /* the root of the whole tree */
+                _ => break,
+            }
         }
     }
 
@@ -174,125 +678,738 @@ impl RegisteredFS {
         })
     }
 
-    pub fn vfs_lookup(&mut self, path: &str) -> Result<DentryRef> {
-        self.path_lookup(path, LookupFlag::empty())
+    pub fn vfs_lookup(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+    ) -> Result<DentryRef> {
+        self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)
             .map(|nd| nd.current)
     }
-    pub fn vfs_mkdir(&mut self, path: &str) -> Result<DentryRef> {
-        let mut nd = self.path_lookup(path, LookupFlag::LOOKUP_PARENT)?;
+    pub fn vfs_chdir(&mut self, ctx: &FSContextRef, cred: &Credentials, path: &str) -> Result<()> {
+        let nd = self.path_lookup(
+            ctx,
+            cred,
+            path,
+            LookupFlag::LOOKUP_FOLLOW | LookupFlag::LOOKUP_DIRECTORY,
+        )?;
+        nd.current
+            .read()
+            .get_inode()?
+            .permission(cred, AccessMask::MAY_EXEC)?;
+        let mut ctx = ctx.write();
+        ctx.cwd = nd.current;
+        ctx.cwd_mnt = nd.mnt;
+        Ok(())
+    }
+    pub fn vfs_fchdir(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        file: &FileRef,
+    ) -> Result<()> {
+        let (dentry, mnt) = {
+            let fr = file.read();
+            if fr.inode.get_metadata()?.mode != INodeType::IFDIR {
+                return Err(Error::new(ENOTDIR));
+            }
+            fr.inode.permission(cred, AccessMask::MAY_EXEC)?;
+            (fr.dentry.clone(), fr.mnt.clone())
+        };
+        let mut ctx = ctx.write();
+        ctx.cwd = dentry;
+        ctx.cwd_mnt = mnt;
+        Ok(())
+    }
+    pub fn vfs_mkdir(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        perm: INodePerm,
+    ) -> Result<DentryRef> {
+        let perm = perm & (INodePerm::S_IRWXUGO | INodePerm::S_ISVTX) & !ctx.read().umask;
+        let mut nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_PARENT)?;
         /* if path equals to `/` or the target exist */
-        if nd.paths.len() == 0 || self.lookup_last(&mut nd, LookupFlag::empty()).is_ok() {
+        if nd.paths.is_empty() || self.lookup_last(&mut nd, LookupFlag::empty()).is_ok() {
             Err(Error::new(EEXIST))
         } else {
+            mnt_want_write(&nd.mnt)?;
             let parent = nd.current.clone();
             let parent_inode = parent.read().get_inode()?;
-            parent_inode.mkdir(&parent, nd.paths[nd.cur_ind])
+            may_create(cred, &parent_inode)?;
+            let attr = init_attr(cred, &parent_inode, perm, true)?;
+            let dentry = parent_inode.mkdir(&parent, &nd.paths[nd.cur_ind], &attr)?;
+            self.touch_created(&parent_inode, &dentry)?;
+            Ok(dentry)
         }
     }
-    pub fn vfs_unlink(&mut self, path: &str) -> Result<()> {
-        let mut nd = self.path_lookup(path, LookupFlag::LOOKUP_PARENT)?;
-        if nd.paths.len() == 0 {
+    pub fn vfs_unlink(&mut self, ctx: &FSContextRef, cred: &Credentials, path: &str) -> Result<()> {
+        let mut nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_PARENT)?;
+        if nd.paths.is_empty() {
             /* if path equals to `/` */
             return Err(Error::new(EINVAL));
         }
+        if is_dots(&nd.paths[nd.cur_ind]) {
+            return Err(Error::new(EINVAL));
+        }
         let parent = nd.current.clone();
-        self.lookup_last(&mut nd, LookupFlag::empty())?;
-        let current_inode = nd.current.read().get_inode()?;
-        /* at least for now，you cannot delete a file which is opened by a process */
-        for file in &self.opened_files {
-            if ptr::eq(current_inode.as_ref(), file.read().inode.as_ref()) {
-                return Err(Error::new(EBUSY));
-            }
+        self.lookup_last(&mut nd, LookupFlag::LOOKUP_NOMOUNT)?;
+        if self.is_mountpoint(&nd.current) {
+            return Err(Error::new(EBUSY));
         }
+        mnt_want_write(&nd.mnt)?;
+        let current_inode = nd.current.read().get_inode()?;
+        let parent_inode = parent.read().get_inode()?;
+        may_delete(cred, &parent_inode, &current_inode)?;
         /* if delete directory, it must be empty first */
-        if current_inode.get_metadata().mode == INodeType::IFDIR {
+        if current_inode.get_metadata()?.mode == INodeType::IFDIR {
             let inodes = current_inode.readdir_inodes(&nd.current)?;
-            if inodes.len() != 0 {
+            if !inodes.is_empty() {
                 return Err(Error::new(ENOTEMPTY));
             }
         }
-        let parent_inode = parent.read().get_inode()?;
-        parent_inode.unlink(&parent, nd.paths[nd.cur_ind - 1])
+        parent_inode.unlink(&parent, &nd.paths[nd.cur_ind - 1])?;
+        self.touch(&current_inode, TimeFlag::S_CTIME)?;
+        self.touch(&parent_inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        /* an opened file lives on without a name until it is closed */
+        self.iput(&current_inode)
     }
-    pub fn vfs_create(&mut self, path: &str) -> Result<DentryRef> {
+    pub fn vfs_create(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        perm: INodePerm,
+    ) -> Result<DentryRef> {
         if path.ends_with("/") {
             return Err(Error::new(EISDIR));
         }
-        let mut nd = self.path_lookup(path, LookupFlag::LOOKUP_PARENT)?;
-        let parent = nd.current.clone();
+        let mut nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_PARENT)?;
         if self.lookup_last(&mut nd, LookupFlag::empty()).is_ok() {
             Err(Error::new(EEXIST))
         } else {
+            self.do_create(ctx, &nd, perm)
+        }
+    }
+    /* create a regular file named by the current component of `nd` in `nd.current` */
+    fn do_create(
+        &mut self,
+        ctx: &FSContextRef,
+        nd: &NameIData,
+        perm: INodePerm,
+    ) -> Result<DentryRef> {
+        mnt_want_write(&nd.mnt)?;
+        let parent = nd.current.clone();
+        let parent_inode = parent.read().get_inode()?;
+        may_create(&nd.cred, &parent_inode)?;
+        let attr = init_attr(&nd.cred, &parent_inode, perm & !ctx.read().umask, false)?;
+        let dentry = parent_inode.create(&parent, &nd.paths[nd.cur_ind], &attr)?;
+        self.touch_created(&parent_inode, &dentry)?;
+        Ok(dentry)
+    }
+    pub fn vfs_link(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        oldpath: &str,
+        newpath: &str,
+    ) -> Result<DentryRef> {
+        let old = self.path_lookup(ctx, cred, oldpath, LookupFlag::empty())?;
+        let old_inode = old.current.read().get_inode()?;
+        self.do_link(ctx, cred, &old.current, &old.mnt, &old_inode, newpath)
+    }
+    /* give an opened file a new name, like linkat with AT_EMPTY_PATH in linux */
+    pub fn vfs_link_file(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        file: &FileRef,
+        newpath: &str,
+    ) -> Result<DentryRef> {
+        let (old_dentry, old_mnt, old_inode, linkable) = {
+            let fr = file.read();
+            (
+                fr.dentry.clone(),
+                fr.mnt.clone(),
+                fr.inode.clone(),
+                fr.linkable,
+            )
+        };
+        /* a file which lost all its names can not come back, except the one
+         * created by `vfs_tmpfile` */
+        if old_inode.get_metadata()?.nlink == 0 && !linkable {
+            return Err(Error::new(ENOENT));
+        }
+        let dentry = self.do_link(ctx, cred, &old_dentry, &old_mnt, &old_inode, newpath)?;
+        file.write().linkable = false;
+        Ok(dentry)
+    }
+    fn do_link(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        old_dentry: &DentryRef,
+        old_mnt: &MountRef,
+        old_inode: &INodeRef,
+        newpath: &str,
+    ) -> Result<DentryRef> {
+        /* hard links to directories are not allowed */
+        if old_inode.get_metadata()?.mode == INodeType::IFDIR {
+            return Err(Error::new(EPERM));
+        }
+        let mut nd = self.path_lookup(ctx, cred, newpath, LookupFlag::LOOKUP_PARENT)?;
+        let parent = nd.current.clone();
+        if nd.paths.is_empty() || self.lookup_last(&mut nd, LookupFlag::empty()).is_ok() {
+            return Err(Error::new(EEXIST));
+        }
+        if !Arc::ptr_eq(old_mnt, &nd.mnt) {
+            return Err(Error::new(EXDEV));
+        }
+        mnt_want_write(&nd.mnt)?;
+        let parent_inode = parent.read().get_inode()?;
+        may_create(cred, &parent_inode)?;
+        let dentry = parent_inode.link(&parent, &nd.paths[nd.cur_ind], old_dentry)?;
+        self.touch(old_inode, TimeFlag::S_CTIME)?;
+        self.touch(&parent_inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        Ok(dentry)
+    }
+    pub fn vfs_rename(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        oldpath: &str,
+        newpath: &str,
+        flags: RenameFlag,
+    ) -> Result<()> {
+        if flags.contains(RenameFlag::RENAME_NOREPLACE | RenameFlag::RENAME_EXCHANGE) {
+            return Err(Error::new(EINVAL));
+        }
+        let mut old_nd = self.path_lookup(ctx, cred, oldpath, LookupFlag::LOOKUP_PARENT)?;
+        let mut new_nd = self.path_lookup(ctx, cred, newpath, LookupFlag::LOOKUP_PARENT)?;
+        /* if one of the paths equals to `/` */
+        if old_nd.paths.is_empty() || new_nd.paths.is_empty() {
+            return Err(Error::new(EBUSY));
+        }
+        let old_name = old_nd.paths[old_nd.cur_ind].clone();
+        let new_name = new_nd.paths[new_nd.cur_ind].clone();
+        if is_dots(&old_name) || is_dots(&new_name) {
+            return Err(Error::new(EINVAL));
+        }
+        if !Arc::ptr_eq(&old_nd.mnt, &new_nd.mnt) {
+            return Err(Error::new(EXDEV));
+        }
+        let old_parent = old_nd.current.clone();
+        let new_parent = new_nd.current.clone();
+        self.lookup_last(&mut old_nd, LookupFlag::LOOKUP_NOMOUNT)?;
+        let old_dentry = old_nd.current.clone();
+        let new_dentry = match self.lookup_last(&mut new_nd, LookupFlag::LOOKUP_NOMOUNT) {
+            Ok(()) => Some(new_nd.current.clone()),
+            Err(err) if err == Error::new(ENOENT) => None,
+            Err(err) => return Err(err),
+        };
+        /* a mountpoint can neither be moved nor replaced */
+        if self.is_mountpoint(&old_dentry)
+            || new_dentry
+                .as_ref()
+                .is_some_and(|new| self.is_mountpoint(new))
+        {
+            return Err(Error::new(EBUSY));
+        }
+        mnt_want_write(&old_nd.mnt)?;
+
+        let old_inode = old_dentry.read().get_inode()?;
+        let old_is_dir = old_inode.get_metadata()?.mode == INodeType::IFDIR;
+        let old_parent_inode = old_parent.read().get_inode()?;
+        let new_parent_inode = new_parent.read().get_inode()?;
+        may_delete(cred, &old_parent_inode, &old_inode)?;
+        match &new_dentry {
+            Some(new_dentry) => {
+                may_delete(cred, &new_parent_inode, &new_dentry.read().get_inode()?)?
+            }
+            None => may_create(cred, &new_parent_inode)?,
+        }
+        /* moving a directory to another parent rewrites its `..` entry */
+        if old_is_dir && !Arc::ptr_eq(&old_parent, &new_parent) {
+            old_inode.permission(cred, AccessMask::MAY_WRITE)?;
+        }
+        match &new_dentry {
+            None => {
+                if flags.contains(RenameFlag::RENAME_EXCHANGE) {
+                    return Err(Error::new(ENOENT));
+                }
+            }
+            Some(new_dentry) => {
+                if flags.contains(RenameFlag::RENAME_NOREPLACE) {
+                    return Err(Error::new(EEXIST));
+                }
+                let new_inode = new_dentry.read().get_inode()?;
+                /* both names refer to the same file, nothing to do */
+                if new_inode.get_ino() == old_inode.get_ino() {
+                    return Ok(());
+                }
+                let new_is_dir = new_inode.get_metadata()?.mode == INodeType::IFDIR;
+                if flags.contains(RenameFlag::RENAME_EXCHANGE) {
+                    if new_is_dir && is_ancestor(new_dentry, &old_parent) {
+                        return Err(Error::new(EINVAL));
+                    }
+                    /* the exchanged directory gets a new `..` entry as well */
+                    if new_is_dir && !Arc::ptr_eq(&old_parent, &new_parent) {
+                        new_inode.permission(cred, AccessMask::MAY_WRITE)?;
+                    }
+                } else if old_is_dir && !new_is_dir {
+                    return Err(Error::new(ENOTDIR));
+                } else if !old_is_dir && new_is_dir {
+                    return Err(Error::new(EISDIR));
+                } else if new_is_dir && !new_inode.readdir_inodes(new_dentry)?.is_empty() {
+                    return Err(Error::new(ENOTEMPTY));
+                }
+            }
+        }
+        /* a directory can not be moved into its own subtree */
+        if old_is_dir && is_ancestor(&old_dentry, &new_parent) {
+            return Err(Error::new(EINVAL));
+        }
+
+        old_parent_inode.rename(&old_parent, &old_name, &new_parent, &new_name, flags)?;
+        self.touch(&old_parent_inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        self.touch(&new_parent_inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        self.touch(&old_inode, TimeFlag::S_CTIME)?;
+        if let Some(new_dentry) = &new_dentry {
+            let new_inode = new_dentry.read().get_inode()?;
+            self.touch(&new_inode, TimeFlag::S_CTIME)?;
+            if !flags.contains(RenameFlag::RENAME_EXCHANGE) {
+                self.iput(&new_inode)?;
+            }
+        }
+
+        /* keep the dentry cache consistent with the filesystem */
+        match new_dentry {
+            Some(new_dentry) if flags.contains(RenameFlag::RENAME_EXCHANGE) => {
+                d_move(&new_dentry, &old_parent, &old_name);
+                d_move(&old_dentry, &new_parent, &new_name);
+            }
+            Some(new_dentry) => {
+                /* the replaced target must not be found by its old name anymore */
+                d_drop(&new_dentry);
+                old_parent.write().subdirs.remove(&old_name);
+                d_move(&old_dentry, &new_parent, &new_name);
+            }
+            None => {
+                old_parent.write().subdirs.remove(&old_name);
+                d_move(&old_dentry, &new_parent, &new_name);
+            }
+        }
+        Ok(())
+    }
+    pub fn vfs_symlink(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        target: &str,
+        path: &str,
+    ) -> Result<DentryRef> {
+        if target.is_empty() {
+            return Err(Error::new(ENOENT));
+        }
+        let mut nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_PARENT)?;
+        let parent = nd.current.clone();
+        if nd.paths.is_empty() || self.lookup_last(&mut nd, LookupFlag::empty()).is_ok() {
+            Err(Error::new(EEXIST))
+        } else {
+            mnt_want_write(&nd.mnt)?;
             let parent_inode = parent.read().get_inode()?;
-            parent_inode.create(&parent, nd.paths[nd.cur_ind])
+            may_create(cred, &parent_inode)?;
+            /* the permission bits of a symbolic link are not used */
+            let attr = init_attr(cred, &parent_inode, INodePerm::S_IRWXUGO, false)?;
+            let dentry = parent_inode.symlink(&parent, &nd.paths[nd.cur_ind], target, &attr)?;
+            self.touch_created(&parent_inode, &dentry)?;
+            Ok(dentry)
+        }
+    }
+    pub fn vfs_readlink(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<usize> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::empty())?;
+        let inode = nd.current.read().get_inode()?;
+        if inode.get_metadata()?.mode != INodeType::IFLNK {
+            return Err(Error::new(EINVAL));
         }
+        /* the content is truncated if `buf` is too small, and no '\0' is appended */
+        let target = inode.readlink(&nd.current)?;
+        let len = core::cmp::min(buf.len(), target.len());
+        buf[0..len].clone_from_slice(&target.as_bytes()[0..len]);
+        Ok(len)
     }
 
-    pub fn vfs_open(&mut self, path: &str, mode: FileMode) -> Result<FileRef> {
-        // TODO: check `mode`
+    pub fn vfs_open(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        mode: FileMode,
+        perm: INodePerm,
+    ) -> Result<FileRef> {
         // TODO: search in self.opened_files
-        let nd = self.path_lookup(path, LookupFlag::empty())?;
+        let creat = mode.contains(FileMode::O_CREAT);
+        if creat && path.ends_with('/') {
+            return Err(Error::new(EISDIR));
+        }
+        /* look up the parent first, so that the last component can be created
+         * within the same lookup if it is missing */
+        let mut nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_PARENT)?;
+        let mut created = false;
+        if nd.cur_ind < nd.paths.len() {
+            let flags = if creat {
+                /* with O_EXCL even a symbolic link counts as existing */
+                if mode.contains(FileMode::O_EXCL) {
+                    LookupFlag::empty()
+                } else {
+                    LookupFlag::LOOKUP_FOLLOW
+                }
+            } else if path.ends_with('/') {
+                LookupFlag::LOOKUP_FOLLOW | LookupFlag::LOOKUP_DIRECTORY
+            } else {
+                LookupFlag::LOOKUP_FOLLOW
+            };
+            match self.lookup_last(&mut nd, flags) {
+                Ok(()) if creat && mode.contains(FileMode::O_EXCL) => {
+                    return Err(Error::new(EEXIST))
+                }
+                Ok(()) => {}
+                /* only the last component may be missing, a dangling link
+                 * leaves `nd` at the missing target */
+                Err(err)
+                    if creat && err == Error::new(ENOENT) && nd.cur_ind + 1 == nd.paths.len() =>
+                {
+                    nd.current = self.do_create(ctx, &nd, perm)?;
+                    created = true;
+                }
+                Err(err) => return Err(err),
+            }
+        }
         let lookup_result = nd.current;
         let inode = lookup_result
             .read()
             .inode
             .upgrade()
             .ok_or_else(|| Error::new(ENOENT))?;
-        if mode.contains(FileMode::O_DIRECTORY) {
-            if inode.get_metadata().mode != INodeType::IFDIR {
-                return Err(Error::new(ENOTDIR));
+        let inode_mode = inode.get_metadata()?.mode;
+        if mode.contains(FileMode::O_DIRECTORY) && inode_mode != INodeType::IFDIR {
+            return Err(Error::new(ENOTDIR));
+        }
+        /* O_TRUNC needs write access as well */
+        let mut mask = open_mask(mode);
+        if mode.contains(FileMode::O_TRUNC) {
+            mask |= AccessMask::MAY_WRITE;
+        }
+        if inode_mode == INodeType::IFDIR && (creat || mask.contains(AccessMask::MAY_WRITE)) {
+            return Err(Error::new(EISDIR));
+        }
+        let is_dev = inode_mode == INodeType::IFCHR || inode_mode == INodeType::IFBLK;
+        if is_dev && nd.mnt.read().flags.contains(MountFlag::MS_NODEV) {
+            return Err(Error::new(EACCES));
+        }
+        /* the permission of a new file is not checked against its own mode */
+        if !created {
+            /* devices are not stored in the filesystem, so they can still be written */
+            if mask.contains(AccessMask::MAY_WRITE) && !is_dev {
+                mnt_want_write(&nd.mnt)?;
+            }
+            inode.permission(cred, mask)?;
+            if mode.contains(FileMode::O_TRUNC) && inode_mode == INodeType::IFREG {
+                let attr = INodeAttr {
+                    valid: AttrMask::ATTR_SIZE | AttrMask::ATTR_MTIME,
+                    size: 0,
+                    mtime: self.current_time(),
+                    ..Default::default()
+                };
+                self.notify_change(&lookup_result, &inode, &attr)?;
             }
         }
-        let file = Arc::new(RwLock::new(File::new(path.to_string(), 0, 0, inode, mode)));
+        self.do_open_file(path, nd.mnt, lookup_result, inode, mode)
+    }
+
+    /* open a program to be executed, which must be a regular file with execute
+     * permission on a mount without MS_NOEXEC, see `open_exec` in linux */
+    pub fn vfs_open_exec(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+    ) -> Result<FileRef> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)?;
+        let inode = nd.current.read().get_inode()?;
+        if inode.get_metadata()?.mode != INodeType::IFREG
+            || nd.mnt.read().flags.contains(MountFlag::MS_NOEXEC)
+        {
+            return Err(Error::new(EACCES));
+        }
+        inode.permission(cred, AccessMask::MAY_EXEC)?;
+        self.do_open_file(path, nd.mnt, nd.current, inode, FileMode::O_RDONLY)
+    }
+
+    /* the credentials a program opened by `vfs_open_exec` runs with, its set user id
+     * and set group id bits take effect unless it is on a mount with MS_NOSUID, see
+     * `bprm_fill_uid` in linux */
+    pub fn vfs_exec_cred(&mut self, file: &FileRef, cred: &Credentials) -> Result<Credentials> {
+        let (mnt, inode) = {
+            let fr = file.read();
+            (fr.mnt.clone(), fr.inode.clone())
+        };
+        let mut exec_cred = cred.clone();
+        if !mnt.read().may_suid() {
+            return Ok(exec_cred);
+        }
+        let md = inode.get_metadata()?;
+        if md.perm.contains(INodePerm::S_ISUID) {
+            exec_cred.uid = md.uid;
+        }
+        /* without group execute permission the bit marks mandatory locking */
+        if md.perm.contains(INodePerm::S_ISGID | INodePerm::S_IXGRP) {
+            exec_cred.gid = md.gid;
+        }
+        Ok(exec_cred)
+    }
+
+    pub fn vfs_lseek(&mut self, file: &FileRef, offset: isize, whence: Whence) -> Result<usize> {
+        let fop = file.read().fop.clone();
+        let pos = fop.llseek(file, offset, whence)?;
+        file.write().pos = pos;
+        Ok(pos)
+    }
+
+    /* create an unnamed regular file in the directory `path`, it is freed with
+     * the last close unless it is given a name by `vfs_link_file` first, which
+     * O_EXCL forbids, see O_TMPFILE in linux */
+    pub fn vfs_tmpfile(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        mode: FileMode,
+        perm: INodePerm,
+    ) -> Result<FileRef> {
+        /* a file nobody can write to would be useless */
+        if !is_writable(mode) {
+            return Err(Error::new(EINVAL));
+        }
+        let nd = self.path_lookup(
+            ctx,
+            cred,
+            path,
+            LookupFlag::LOOKUP_FOLLOW | LookupFlag::LOOKUP_DIRECTORY,
+        )?;
+        mnt_want_write(&nd.mnt)?;
+        let dir_inode = nd.current.read().get_inode()?;
+        may_create(cred, &dir_inode)?;
+        let attr = init_attr(cred, &dir_inode, perm & !ctx.read().umask, false)?;
+        let dentry = dir_inode.tmpfile(&nd.current, &attr)?;
+        let inode = dentry.read().get_inode()?;
+        self.touch(&inode, TimeFlag::all())?;
+        let file = self.do_open_file(path, nd.mnt, dentry, inode, mode)?;
+        file.write().linkable = !mode.contains(FileMode::O_EXCL);
+        Ok(file)
+    }
+
+    /* set up an open file with the file operations of `inode` */
+    fn do_open_file(
+        &mut self,
+        path: &str,
+        mnt: MountRef,
+        dentry: DentryRef,
+        inode: INodeRef,
+        mode: FileMode,
+    ) -> Result<FileRef> {
+        let fop = inode.get_fop();
+        let file = Arc::new(RwLock::new(File::new(
+            path.to_string(),
+            0,
+            0,
+            mnt,
+            dentry,
+            inode.clone(),
+            fop.clone(),
+            mode,
+        )));
+        if let Err(err) = fop.open(&inode, &file) {
+            /* the inode may be an unlinked one which nobody else holds */
+            self.iput(&inode)?;
+            return Err(err);
+        }
         self.opened_files.push(file.clone());
-        return Ok(file);
+        Ok(file)
+    }
+
+    /* called when a descriptor of `file` is closed but other references remain */
+    pub fn vfs_flush(&mut self, file: &FileRef) -> Result<()> {
+        let fop = file.read().fop.clone();
+        fop.flush(file)
     }
 
+    /* the file is released even if the flush fails, whose error is still reported */
     pub fn vfs_close(&mut self, file: &FileRef) -> Result<()> {
+        let flushed = self.vfs_flush(file);
         for i in 0..self.opened_files.len() {
             if ptr::eq(file.as_ref(), self.opened_files.get(i).unwrap().as_ref()) {
                 self.opened_files.remove(i);
                 break;
             }
         }
-        Ok(())
+        let (inode, fop) = {
+            let fr = file.read();
+            (fr.inode.clone(), fr.fop.clone())
+        };
+        /* the file is gone either way, so the inode is always put and the first error is kept */
+        let released = fop.release(&inode, file);
+        let put = self.iput(&inode);
+        flushed.and(released).and(put)
     }
 
     pub fn vfs_write(&mut self, file: &FileRef, buf: &[u8]) -> Result<usize> {
         // TODO: check buf address is safe to read
-        /* check write */
-        {
-            let fr = file.read();
-            /* at least for now，you can only call write to a regular file */
-            if fr.inode.get_metadata().mode != INodeType::IFREG {
-                return Err(Error::new(EINVAL));
+        let (inode, fop) = check_write(file)?;
+        let len = fop.write(file, buf)?;
+        if len > 0 {
+            self.touch(&inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        }
+        Ok(len)
+    }
+
+    pub fn vfs_read(&mut self, file: &FileRef, buf: &mut [u8]) -> Result<usize> {
+        // TODO: check buf address is safe to write
+        let (_, fop) = check_read(file)?;
+        let len = fop.read(file, buf)?;
+        self.file_accessed(file)?;
+        Ok(len)
+    }
+
+    /* write at `offset` without using or changing the file position, O_APPEND
+     * is ignored as required by posix */
+    pub fn vfs_pwrite(&mut self, file: &FileRef, buf: &[u8], offset: usize) -> Result<usize> {
+        let (inode, fop) = check_write(file)?;
+        let len = fop.write_at(file, buf, offset)?;
+        if len > 0 {
+            self.touch(&inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
+        }
+        Ok(len)
+    }
+
+    /* read at `offset` without using or changing the file position */
+    pub fn vfs_pread(&mut self, file: &FileRef, buf: &mut [u8], offset: usize) -> Result<usize> {
+        let (_, fop) = check_read(file)?;
+        let len = fop.read_at(file, buf, offset)?;
+        self.file_accessed(file)?;
+        Ok(len)
+    }
+
+    /* write the buffers one after another, the file position is locked during
+     * the whole operation so that no other read or write on the same open file
+     * can interleave */
+    pub fn vfs_writev(&mut self, file: &FileRef, iov: &[&[u8]]) -> Result<usize> {
+        let (inode, fop) = check_write(file)?;
+        let total = {
+            let mut fw = file.write();
+            if fw.mode.contains(FileMode::O_APPEND) {
+                fw.pos = inode.get_metadata()?.size;
             }
-            if !(fr.mode.contains(FileMode::O_WRONLY)
-                || fr.mode.contains(FileMode::O_RDWR)
-                || fr.mode.contains(FileMode::O_APPEND))
-            {
-                return Err(Error::new(EBADF));
+            let mut total = 0;
+            for buf in iov {
+                let len = match fop.write_at(file, buf, fw.pos + total) {
+                    Ok(len) => len,
+                    /* report the bytes already written */
+                    Err(_) if total > 0 => break,
+                    Err(err) => return Err(err),
+                };
+                total += len;
+                if len < buf.len() {
+                    break;
+                }
             }
+            fw.pos += total;
+            total
+        };
+        if total > 0 {
+            self.touch(&inode, TimeFlag::S_MTIME | TimeFlag::S_CTIME)?;
         }
-        let inode = file.read().inode.clone();
-        inode.write(file, buf)
+        Ok(total)
     }
 
-    pub fn vfs_read(&mut self, file: &FileRef, buf: &mut [u8]) -> Result<usize> {
-        // TODO: check buf address is safe to write
-        /* check read */
-        {
-            let fr = file.read();
-            /* at least for now，you can only call read to a regular file */
-            if fr.inode.get_metadata().mode != INodeType::IFREG {
-                return Err(Error::new(EINVAL));
+    /* fill the buffers one after another, see `vfs_writev` */
+    pub fn vfs_readv(&mut self, file: &FileRef, iov: &mut [&mut [u8]]) -> Result<usize> {
+        let (_, fop) = check_read(file)?;
+        let total = {
+            let mut fw = file.write();
+            let mut total = 0;
+            for buf in iov.iter_mut() {
+                let len = match fop.read_at(file, buf, fw.pos + total) {
+                    Ok(len) => len,
+                    Err(_) if total > 0 => break,
+                    Err(err) => return Err(err),
+                };
+                total += len;
+                if len < buf.len() {
+                    break;
+                }
             }
-            if !(fr.mode.contains(FileMode::O_RDONLY) || fr.mode.contains(FileMode::O_RDWR)) {
-                return Err(Error::new(EBADF));
+            fw.pos += total;
+            total
+        };
+        self.file_accessed(file)?;
+        Ok(total)
+    }
+
+    pub fn vfs_fsync(&mut self, file: &FileRef) -> Result<()> {
+        let fop = file.read().fop.clone();
+        fop.fsync(file, false)
+    }
+
+    /* same as `vfs_fsync`, but skips metadata which is not needed to read the data */
+    pub fn vfs_fdatasync(&mut self, file: &FileRef) -> Result<()> {
+        let fop = file.read().fop.clone();
+        fop.fsync(file, true)
+    }
+
+    pub fn vfs_statfs(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        buf: &mut StatFs,
+    ) -> Result<()> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)?;
+        do_statfs(&nd.mnt, buf)
+    }
+
+    pub fn vfs_fstatfs(&mut self, file: &FileRef, buf: &mut StatFs) -> Result<()> {
+        let mnt = file.read().mnt.clone();
+        do_statfs(&mnt, buf)
+    }
+
+    /* sync the filesystem containing `file` */
+    pub fn vfs_syncfs(&mut self, file: &FileRef) -> Result<()> {
+        let fs = file.read().inode.get_fs();
+        fs.sync_fs(true)
+    }
+
+    /* sync every mounted filesystem, one failing does not stop the others,
+     * and the first error is returned */
+    pub fn vfs_sync(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for (_, mounted_fss) in self.mount_infos.values() {
+            for fs in mounted_fss {
+                match fs.sync_fs(true) {
+                    Err(err) if result.is_ok() => result = Err(err),
+                    _ => {}
+                }
             }
         }
-        let inode = file.read().inode.clone();
-        inode.read(file, buf)
+        result
     }
 
     pub fn vfs_readdir(&mut self, file: &FileRef, dirs: &mut [Direntory]) -> Result<usize> {
@@ -301,18 +1418,195 @@ impl RegisteredFS {
         {
             let fr = file.read();
             /* muse be a directory */
-            if fr.inode.get_metadata().mode != INodeType::IFDIR {
+            if fr.inode.get_metadata()?.mode != INodeType::IFDIR {
                 return Err(Error::new(EINVAL));
             }
             if !(fr.mode.contains(FileMode::O_RDONLY) || fr.mode.contains(FileMode::O_RDWR)) {
                 return Err(Error::new(EBADF));
             }
         }
-        let inode = file.read().inode.clone();
-        inode.readdir(file, dirs)
+        /* fill `.` and `..` first, the filesystem only lists real entries */
+        let mut count = 0;
+        while count < dirs.len() && file.read().pos < DIR_DOT_ENTRIES {
+            let (pos, ino) = {
+                let fr = file.read();
+                let ino = if fr.pos == 0 {
+                    fr.inode.get_ino()
+                } else {
+                    /* `..` of the root of a filesystem is itself */
+                    let parent = fr.dentry.read().parent.upgrade();
+                    match parent {
+                        Some(parent) => parent.read().get_inode()?.get_ino(),
+                        None => fr.inode.get_ino(),
+                    }
+                };
+                (fr.pos, ino)
+            };
+            let name = if pos == 0 { "." } else { ".." };
+            fill_direntory(&mut dirs[count], ino, pos, name);
+            file.write().pos += 1;
+            count += 1;
+        }
+        let fop = file.read().fop.clone();
+        count += fop.readdir(file, &mut dirs[count..])?;
+        self.file_accessed(file)?;
+        Ok(count)
+    }
+    pub fn vfs_stat(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        stat: &mut Stat,
+    ) -> Result<()> {
+        self.do_stat(ctx, cred, path, stat, LookupFlag::LOOKUP_FOLLOW)
+    }
+    pub fn vfs_chmod(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        perm: INodePerm,
+    ) -> Result<()> {
+        let attr = INodeAttr {
+            valid: AttrMask::ATTR_MODE,
+            perm,
+            ..Default::default()
+        };
+        self.do_setattr(ctx, cred, path, attr)
+    }
+    /* `None` leaves the owner or the group unchanged */
+    pub fn vfs_chown(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        uid: Option<usize>,
+        gid: Option<usize>,
+    ) -> Result<()> {
+        let mut attr = INodeAttr::default();
+        if let Some(uid) = uid {
+            attr.valid |= AttrMask::ATTR_UID;
+            attr.uid = uid;
+        }
+        if let Some(gid) = gid {
+            attr.valid |= AttrMask::ATTR_GID;
+            attr.gid = gid;
+        }
+        self.do_setattr(ctx, cred, path, attr)
+    }
+    pub fn vfs_utimensat(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        atime: UTime,
+        mtime: UTime,
+    ) -> Result<()> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)?;
+        mnt_want_write(&nd.mnt)?;
+        let inode = nd.current.read().get_inode()?;
+        /* setting explicit times needs ownership, setting them to now also
+         * works with write permission */
+        if !is_owner(cred, &inode.get_metadata()?) {
+            match (atime, mtime) {
+                (UTime::At(_), _) | (_, UTime::At(_)) => return Err(Error::new(EPERM)),
+                _ => inode.permission(cred, AccessMask::MAY_WRITE)?,
+            }
+        }
+        let now = self.current_time();
+        let mut attr = INodeAttr::default();
+        if let Some(atime) = atime.resolve(now) {
+            attr.valid |= AttrMask::ATTR_ATIME;
+            attr.atime = atime;
+        }
+        if let Some(mtime) = mtime.resolve(now) {
+            attr.valid |= AttrMask::ATTR_MTIME;
+            attr.mtime = mtime;
+        }
+        self.notify_change(&nd.current, &inode, &attr)
+    }
+    pub fn vfs_truncate(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        size: usize,
+    ) -> Result<()> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)?;
+        let inode = nd.current.read().get_inode()?;
+        match inode.get_metadata()?.mode {
+            INodeType::IFREG => {}
+            INodeType::IFDIR => return Err(Error::new(EISDIR)),
+            _ => return Err(Error::new(EINVAL)),
+        }
+        mnt_want_write(&nd.mnt)?;
+        inode.permission(cred, AccessMask::MAY_WRITE)?;
+        let attr = INodeAttr {
+            valid: AttrMask::ATTR_SIZE | AttrMask::ATTR_MTIME,
+            size,
+            mtime: self.current_time(),
+            ..Default::default()
+        };
+        self.notify_change(&nd.current, &inode, &attr)
+    }
+    pub fn vfs_ftruncate(&mut self, file: &FileRef, size: usize) -> Result<()> {
+        let (mnt, dentry, inode) = {
+            let fr = file.read();
+            if !is_writable(fr.mode) || fr.inode.get_metadata()?.mode != INodeType::IFREG {
+                return Err(Error::new(EINVAL));
+            }
+            (fr.mnt.clone(), fr.dentry.clone(), fr.inode.clone())
+        };
+        /* the mount may have become read only since the file was opened */
+        mnt_want_write(&mnt)?;
+        let attr = INodeAttr {
+            valid: AttrMask::ATTR_SIZE | AttrMask::ATTR_MTIME,
+            size,
+            mtime: self.current_time(),
+            ..Default::default()
+        };
+        self.notify_change(&dentry, &inode, &attr)
+    }
+    fn do_setattr(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        mut attr: INodeAttr,
+    ) -> Result<()> {
+        let nd = self.path_lookup(ctx, cred, path, LookupFlag::LOOKUP_FOLLOW)?;
+        mnt_want_write(&nd.mnt)?;
+        let inode = nd.current.read().get_inode()?;
+        setattr_prepare(cred, &inode.get_metadata()?, &mut attr)?;
+        self.notify_change(&nd.current, &inode, &attr)
+    }
+    pub fn vfs_fstat(&mut self, file: &FileRef, stat: &mut Stat) -> Result<()> {
+        let (dentry, inode) = {
+            let fr = file.read();
+            (fr.dentry.clone(), fr.inode.clone())
+        };
+        inode.getattr(&dentry, stat)
     }
-    pub fn vfs_stat(&mut self, path: &str, stat: &mut Stat) -> Result<()> {
-        let nd = self.path_lookup(path, LookupFlag::empty())?;
+    /* same as `vfs_stat`, but a symbolic link itself is reported */
+    pub fn vfs_lstat(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        stat: &mut Stat,
+    ) -> Result<()> {
+        self.do_stat(ctx, cred, path, stat, LookupFlag::empty())
+    }
+    fn do_stat(
+        &mut self,
+        ctx: &FSContextRef,
+        cred: &Credentials,
+        path: &str,
+        stat: &mut Stat,
+        flags: LookupFlag,
+    ) -> Result<()> {
+        let nd = self.path_lookup(ctx, cred, path, flags)?;
         let inode = nd
             .current
             .read()
@@ -323,25 +1617,575 @@ impl RegisteredFS {
     }
 }
 
-struct NameIData<'nd> {
+fn is_writable(mode: FileMode) -> bool {
+    mode.contains(FileMode::O_WRONLY)
+        || mode.contains(FileMode::O_RDWR)
+        || mode.contains(FileMode::O_APPEND)
+}
+
+/* the default `llseek`, a regular file is treated as a single data region
+ * followed by a hole at its end, and the offset of a directory is the index of
+ * an entry as used by `readdir`, see `generic_file_llseek` in linux */
+pub fn generic_file_llseek(file: &FileRef, offset: isize, whence: Whence) -> Result<usize> {
+    let (pos, metadata) = {
+        let fr = file.read();
+        (fr.pos, fr.inode.get_metadata()?)
+    };
+    let base = match (metadata.mode, whence) {
+        (_, Whence::Set) => 0,
+        (_, Whence::Cur) => pos,
+        /* there is no end, data or hole in a directory */
+        (INodeType::IFDIR, _) => return Err(Error::new(EINVAL)),
+        (_, Whence::End) => metadata.size,
+        (_, Whence::Data) | (_, Whence::Hole) => {
+            if offset < 0 || offset as usize >= metadata.size {
+                return Err(Error::new(ENXIO));
+            }
+            return Ok(match whence {
+                Whence::Data => offset as usize,
+                _ => metadata.size,
+            });
+        }
+    };
+    let pos = if offset < 0 {
+        base.checked_sub(offset.wrapping_neg() as usize)
+    } else {
+        base.checked_add(offset as usize)
+    };
+    /* the result can not be negative */
+    pos.ok_or_else(|| Error::new(EINVAL))
+}
+
+fn do_statfs(mnt: &MountRef, buf: &mut StatFs) -> Result<()> {
+    let mnt = mnt.read();
+    *buf = Default::default();
+    mnt.fs.statfs(buf)?;
+    buf.flags = mnt.flags | mnt.fs.get_flags();
+    Ok(())
+}
+
+/* the flags kept by the superblock, the others belong to a single mount */
+fn sb_flags(flags: MountFlag) -> MountFlag {
+    flags & (MountFlag::MS_RDONLY | MountFlag::MS_SYNCHRONOUS)
+}
+
+/* fail with EROFS if nothing may be changed under `mnt`, see `mnt_want_write` in linux */
+fn mnt_want_write(mnt: &MountRef) -> Result<()> {
+    if mnt.read().is_readonly() {
+        return Err(Error::new(EROFS));
+    }
+    Ok(())
+}
+
+/* check that `file` can be written, and return its inode and file operations */
+fn check_write(file: &FileRef) -> Result<(INodeRef, FileOperationsRef)> {
+    let fr = file.read();
+    /* directories only change through the namespace operations */
+    if fr.inode.get_metadata()?.mode == INodeType::IFDIR {
+        return Err(Error::new(EINVAL));
+    }
+    if !is_writable(fr.mode) {
+        return Err(Error::new(EBADF));
+    }
+    Ok((fr.inode.clone(), fr.fop.clone()))
+}
+
+/* check that `file` can be read, and return its inode and file operations */
+fn check_read(file: &FileRef) -> Result<(INodeRef, FileOperationsRef)> {
+    let fr = file.read();
+    /* directories are read by readdir */
+    if fr.inode.get_metadata()?.mode == INodeType::IFDIR {
+        return Err(Error::new(EINVAL));
+    }
+    if !(fr.mode.contains(FileMode::O_RDONLY) || fr.mode.contains(FileMode::O_RDWR)) {
+        return Err(Error::new(EBADF));
+    }
+    Ok((fr.inode.clone(), fr.fop.clone()))
+}
+
+/* the access needed to open a file with `mode` */
+fn open_mask(mode: FileMode) -> AccessMask {
+    let mut mask = AccessMask::empty();
+    if mode.contains(FileMode::O_RDONLY) || mode.contains(FileMode::O_RDWR) {
+        mask |= AccessMask::MAY_READ;
+    }
+    if is_writable(mode) {
+        mask |= AccessMask::MAY_WRITE;
+    }
+    mask
+}
+
+/* check `mask` against the permission bits of an inode, see `generic_permission` in linux */
+pub fn generic_permission(
+    metadata: &INodeMetaData,
+    cred: &Credentials,
+    mask: AccessMask,
+) -> Result<()> {
+    if cred.is_root() {
+        /* root bypasses the checks, except executing a file without any execute bit */
+        if !mask.contains(AccessMask::MAY_EXEC)
+            || metadata.mode == INodeType::IFDIR
+            || metadata
+                .perm
+                .intersects(INodePerm::S_IXUSR | INodePerm::S_IXGRP | INodePerm::S_IXOTH)
+        {
+            return Ok(());
+        }
+        return Err(Error::new(EACCES));
+    }
+    let bits = metadata.perm.bits();
+    let granted = if cred.uid == metadata.uid {
+        bits >> 6
+    } else if cred.in_group(metadata.gid) {
+        bits >> 3
+    } else {
+        bits
+    } & 0o7;
+    if mask.bits() & !granted != 0 {
+        return Err(Error::new(EACCES));
+    }
+    Ok(())
+}
+
+/* the directory being walked must be searchable */
+fn may_lookup(nd: &NameIData) -> Result<()> {
+    let inode = nd.current.read().get_inode()?;
+    inode.permission(&nd.cred, AccessMask::MAY_EXEC)
+}
+
+fn may_create(cred: &Credentials, dir: &INodeRef) -> Result<()> {
+    dir.permission(cred, AccessMask::MAY_WRITE | AccessMask::MAY_EXEC)
+}
+
+/* in a sticky directory only the owner of the file, the owner of the directory
+ * or root may remove or rename the file */
+fn may_delete(cred: &Credentials, dir: &INodeRef, inode: &INodeRef) -> Result<()> {
+    may_create(cred, dir)?;
+    let dir_md = dir.get_metadata()?;
+    if dir_md.perm.contains(INodePerm::S_ISVTX)
+        && !cred.is_root()
+        && cred.uid != dir_md.uid
+        && cred.uid != inode.get_metadata()?.uid
+    {
+        return Err(Error::new(EPERM));
+    }
+    Ok(())
+}
+
+/* the initial owner and permission bits of a new inode in `dir`, a directory
+ * with the set-group-id bit passes its group on, see `inode_init_owner` in linux */
+fn init_attr(
+    cred: &Credentials,
+    dir: &INodeRef,
+    perm: INodePerm,
+    is_dir: bool,
+) -> Result<INodeAttr> {
+    let dir_md = dir.get_metadata()?;
+    let mut perm = perm;
+    let gid = if dir_md.perm.contains(INodePerm::S_ISGID) {
+        if is_dir {
+            perm |= INodePerm::S_ISGID;
+        }
+        dir_md.gid
+    } else {
+        cred.gid
+    };
+    if !is_dir
+        && perm.contains(INodePerm::S_ISGID | INodePerm::S_IXGRP)
+        && !cred.is_root()
+        && !cred.in_group(gid)
+    {
+        perm.remove(INodePerm::S_ISGID);
+    }
+    Ok(INodeAttr {
+        valid: AttrMask::ATTR_MODE | AttrMask::ATTR_UID | AttrMask::ATTR_GID,
+        perm,
+        uid: cred.uid,
+        gid,
+        ..Default::default()
+    })
+}
+
+fn is_owner(cred: &Credentials, metadata: &INodeMetaData) -> bool {
+    cred.is_root() || cred.uid == metadata.uid
+}
+
+/* check whether `cred` may change the attributes of an inode, and drop the
+ * set-id bits that must not survive the change, see `setattr_prepare` in linux */
+fn setattr_prepare(
+    cred: &Credentials,
+    metadata: &INodeMetaData,
+    attr: &mut INodeAttr,
+) -> Result<()> {
+    /* only root can give a file away */
+    if attr.valid.contains(AttrMask::ATTR_UID) && !cred.is_root() && attr.uid != metadata.uid {
+        return Err(Error::new(EPERM));
+    }
+    /* the owner can only change the group to one of its own groups */
+    if attr.valid.contains(AttrMask::ATTR_GID)
+        && !cred.is_root()
+        && (cred.uid != metadata.uid || (attr.gid != metadata.gid && !cred.in_group(attr.gid)))
+    {
+        return Err(Error::new(EPERM));
+    }
+    if attr.valid.contains(AttrMask::ATTR_MODE) {
+        if !is_owner(cred, metadata) {
+            return Err(Error::new(EPERM));
+        }
+        let gid = if attr.valid.contains(AttrMask::ATTR_GID) {
+            attr.gid
+        } else {
+            metadata.gid
+        };
+        if !cred.is_root() && !cred.in_group(gid) {
+            attr.perm.remove(INodePerm::S_ISGID);
+        }
+    }
+    /* changing the owner of a regular file clears its set-id bits */
+    if attr
+        .valid
+        .intersects(AttrMask::ATTR_UID | AttrMask::ATTR_GID)
+        && !cred.is_root()
+        && metadata.mode == INodeType::IFREG
+    {
+        if !attr.valid.contains(AttrMask::ATTR_MODE) {
+            attr.valid |= AttrMask::ATTR_MODE;
+            attr.perm = metadata.perm;
+        }
+        attr.perm.remove(INodePerm::S_ISUID | INodePerm::S_ISGID);
+    }
+    Ok(())
+}
+
+fn is_dots(name: &str) -> bool {
+    name == "." || name == ".."
+}
+
+/* whether `ancestor` is `dentry` itself or one of its parents */
+fn is_ancestor(ancestor: &DentryRef, dentry: &DentryRef) -> bool {
+    let mut current = Some(dentry.clone());
+    while let Some(dentry) = current {
+        if Arc::ptr_eq(ancestor, &dentry) {
+            return true;
+        }
+        current = dentry.read().parent.upgrade();
+    }
+    false
+}
+
+/* attach `dentry` to `parent` with `name`, a cached entry of the same name is replaced */
+fn d_move(dentry: &DentryRef, parent: &DentryRef, name: &str) {
+    parent
+        .write()
+        .subdirs
+        .insert(name.to_string(), Arc::downgrade(dentry));
+    dentry.write().parent = Arc::downgrade(parent);
+}
+
+/* remove `dentry` from the cache of its parent and detach it from there */
+fn d_drop(dentry: &DentryRef) {
+    let (parent, name) = {
+        let dr = dentry.read();
+        let parent = match dr.parent.upgrade() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let name = parent
+            .read()
+            .subdirs
+            .iter()
+            .find(|(_, child)| child.ptr_eq(&Arc::downgrade(dentry)))
+            .map(|(name, _)| name.clone());
+        (parent, name)
+    };
+    if let Some(name) = name {
+        parent.write().subdirs.remove(&name);
+    }
+    dentry.write().parent = Weak::new();
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn fill_direntory(dir: &mut Direntory, ino: usize, off: usize, name: &str) {
+    dir.ino = ino;
+    dir.off = off;
+    dir.name_len = name.len();
+    dir.name[0..name.len()].clone_from_slice(name.as_bytes());
+    dir.name[name.len()] = 0;
+}
+
+struct NameIData {
     current: DentryRef,
+    mnt: MountRef,
     root: DentryRef,
-    paths: Vec<&'nd str>,
+    root_mnt: MountRef,
+    /* the credentials used for the permission checks during the walk */
+    cred: Credentials,
+    paths: Vec<String>,
     cur_ind: usize,
+    /* number of symbolic links followed during this lookup */
+    link_count: usize,
+}
+
+/* filesystem information of a task, see `struct fs_struct` in linux */
+#[derive(new, Clone)]
+pub struct FSContext {
+    pub root: DentryRef,
+    pub cwd: DentryRef,
+    pub root_mnt: MountRef,
+    pub cwd_mnt: MountRef,
+    /* permission bits cleared from the mode of new files and directories */
+    #[new(value = "INodePerm::from_bits_truncate(0o022)")]
+    pub umask: INodePerm,
+}
+
+/* identity of a task used in permission checks, see `struct cred` in linux */
+#[derive(new, Clone, Debug, Default)]
+pub struct Credentials {
+    pub uid: usize,
+    pub gid: usize,
+    /* supplementary groups */
+    #[new(default)]
+    pub groups: Vec<usize>,
+}
+
+impl Credentials {
+    pub fn root() -> Credentials {
+        Credentials::new(0, 0)
+    }
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+    pub fn in_group(&self, gid: usize) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/* a mounted instance of a filesystem, see `struct vfsmount` in linux */
+#[derive(new)]
+pub struct Mount {
+    pub fs: FSRef,
+    pub root: DentryRef,
+    /* the mount this one is attached to, and the dentry it covers there */
+    #[new(default)]
+    pub parent: MountWeakRef,
+    #[new(default)]
+    pub mountpoint: Option<DentryRef>,
+    /* the flags of this mount only, the superblock has its own ones */
+    #[new(default)]
+    pub flags: MountFlag,
+    /* set once the mount is removed from the namespace */
+    #[new(default)]
+    umounted: bool,
+    /* shared by every mount of the same superblock, so its count is the
+     * number of those mounts which are still alive */
+    #[new(default)]
+    sb_mounts: Arc<()>,
+}
+
+unsafe impl Send for Mount {}
+
+impl Mount {
+    /* a read only superblock makes all its mounts read only */
+    pub fn is_readonly(&self) -> bool {
+        self.flags.contains(MountFlag::MS_RDONLY)
+            || self.fs.get_flags().contains(MountFlag::MS_RDONLY)
+    }
+    /* whether set user id and set group id bits of programs on this mount take effect */
+    pub fn may_suid(&self) -> bool {
+        !self.flags.contains(MountFlag::MS_NOSUID)
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        /* the last user of the last mount of an unmounted filesystem tears it
+         * down, errors can not be reported to anyone here */
+        if self.umounted && Arc::strong_count(&self.sb_mounts) == 1 {
+            let _ = self.fs.put_super();
+        }
+    }
+}
+
+bitflags! {
+pub struct UmountFlag:u32 {
+    const MNT_DETACH = 0b00000001;      // remove from the namespace now, tear down when unused
+    const UMOUNT_NOFOLLOW = 0b00000010; // do not follow a symbolic link in the target
+}
+}
+
+bitflags! {
+pub struct RenameFlag:u32 {
+    const RENAME_NOREPLACE = 0b00000001;    // don't overwrite the target
+    const RENAME_EXCHANGE = 0b00000010;     // exchange the source and the target
+}
 }
 
 bitflags! {
 struct LookupFlag:u32 {
-    const LOOKUP_FOLLOW = 0b00000001;   // follow link (not currently implemented)
+    const LOOKUP_FOLLOW = 0b00000001;   // follow link in the last component
     const LOOKUP_DIRECTORY = 0b00000010;// search a directory
     const LOOKUP_PARENT = 0b00000100;   // search the parent and ignore the tail
     const LOOKUP_REVAL = 0b00001000;    // search on fs instead of dentry cache (without test)
+    const LOOKUP_NOMOUNT = 0b00010000;  // do not enter a filesystem mounted on the last component
 }
 }
+
+/* the superblock of a mounted filesystem */
 pub trait FileSystem: Send + Sync {
-    // fn alloc_inode(&self, fs: &FSRef) -> Result<INodeRef>;
-    // fn get_inode(&self, ino: usize) -> Result<INodeRef>;
-    fn todo(&self);
+    fn get_root(&self) -> Result<INodeRef>;
+    /* look up an inode by its number, like `iget_locked` in linux */
+    fn get_inode(&self, ino: usize) -> Result<INodeRef>;
+    fn get_flags(&self) -> MountFlag;
+
+    // struct super_operations
+    /* a new inode with `metadata`, which gets its number from the filesystem
+     * and is not linked anywhere yet */
+    fn alloc_inode(&self, metadata: INodeMetaData) -> Result<INodeRef>;
+    //     struct inode *(*alloc_inode)(struct super_block *sb);
+    //     void (*destroy_inode)(struct inode *);
+    //     void (*free_inode)(struct inode *);
+    //     void (*dirty_inode) (struct inode *, int flags);
+    //     int (*write_inode) (struct inode *, struct writeback_control *wbc);
+    //     int (*drop_inode) (struct inode *);
+    /* release an inode and its data, called when the inode has no links and
+     * is not opened anymore */
+    fn evict_inode(&self, inode: &INodeRef) -> Result<()>;
+    //     void (*evict_inode) (struct inode *);
+    /* release everything of the filesystem, nothing may be used after it */
+    fn put_super(&self) -> Result<()>;
+    //     void (*put_super) (struct super_block *);
+    /* write out everything of the filesystem, and with `wait` return only
+     * after it is done */
+    fn sync_fs(&self, wait: bool) -> Result<()>;
+    //     int (*sync_fs)(struct super_block *sb, int wait);
+    //     int (*freeze_super) (struct super_block *);
+    //     int (*freeze_fs) (struct super_block *);
+    //     int (*thaw_super) (struct super_block *);
+    //     int (*unfreeze_fs) (struct super_block *);
+    /* `flags` is left untouched, the vfs fills in `StatFs::flags` */
+    fn statfs(&self, buf: &mut StatFs) -> Result<()>;
+    //     int (*statfs) (struct dentry *, struct kstatfs *);
+    /* change the flags and the filesystem specific options of a mounted filesystem */
+    /* options not given keep their current values */
+    fn remount(&self, opts: &MountOptions) -> Result<()>;
+    //     int (*remount_fs) (struct super_block *, int *, char *);
+    //     void (*umount_begin) (struct super_block *);
+}
+
+/* see `struct kstatfs` in linux */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StatFs {
+    pub bsize: usize,     // size of a block
+    pub blocks: usize,    // number of blocks
+    pub bfree: usize,     // number of free blocks
+    pub bavail: usize,    // number of free blocks for unprivileged users
+    pub files: usize,     // number of inodes
+    pub ffree: usize,     // number of free inodes
+    pub namelen: usize,   // max length of a name
+    pub flags: MountFlag, // flags of the mount
+}
+
+bitflags! {
+pub struct MountFlag:u32 {
+    const MS_RDONLY = 0b00000001;       // read only
+    const MS_NOSUID = 0b00000010;       // ignore set user id and set group id bits
+    const MS_NODEV = 0b00000100;        // no access to device files
+    const MS_NOEXEC = 0b00001000;       // no program execution
+    const MS_SYNCHRONOUS = 0b00010000;  // write synchronously
+    const MS_NOATIME = 0b00100000;      // do not update access times
+    const MS_NODIRATIME = 0b01000000;   // do not update access times of directories
+    const MS_RELATIME = 0b10000000;     // update access times only if they are older than modify or change times
+    const MS_STRICTATIME = 0b100000000; // always update access times, which is the default
+    const MS_BIND = 0b1000000000;       // attach an existing subtree instead of a new filesystem
+    const MS_REC = 0b10000000000;       // with MS_BIND, also attach the mounts below the subtree
+}
+}
+
+impl Default for MountFlag {
+    fn default() -> Self {
+        MountFlag::empty()
+    }
+}
+
+/* the options of a mount given as `ro,size=16m,mode=0755`, the generic ones
+ * are turned into flags and the rest is left to the filesystem, which fails
+ * with EINVAL on any option it does not know */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MountOptions {
+    pub flags: MountFlag,
+    /* filesystem specific options in the given order, `key` or `key=value` */
+    pub options: Vec<(String, Option<String>)>,
+}
+
+impl MountOptions {
+    /* parse `data` on top of `flags` */
+    pub fn parse(flags: MountFlag, data: &str) -> Result<MountOptions> {
+        let mut opts = MountOptions {
+            flags,
+            options: Vec::new(),
+        };
+        let none = MountFlag::empty();
+        let atime = MountFlag::MS_NOATIME | MountFlag::MS_RELATIME;
+        /* MS_STRICTATIME overrides the other access time flags, as in `do_mount` */
+        if opts.flags.contains(MountFlag::MS_STRICTATIME) {
+            opts.flags.remove(atime);
+        }
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let mut kv = option.splitn(2, '=');
+            let key = kv.next().unwrap();
+            let value = kv.next();
+            if key.is_empty() {
+                return Err(Error::new(EINVAL));
+            }
+            /* the flags to set and to clear, a later option wins */
+            let (set, clear) = match (key, value) {
+                ("ro", None) => (MountFlag::MS_RDONLY, none),
+                ("rw", None) => (none, MountFlag::MS_RDONLY),
+                ("nosuid", None) => (MountFlag::MS_NOSUID, none),
+                ("suid", None) => (none, MountFlag::MS_NOSUID),
+                ("nodev", None) => (MountFlag::MS_NODEV, none),
+                ("dev", None) => (none, MountFlag::MS_NODEV),
+                ("noexec", None) => (MountFlag::MS_NOEXEC, none),
+                ("exec", None) => (none, MountFlag::MS_NOEXEC),
+                ("sync", None) => (MountFlag::MS_SYNCHRONOUS, none),
+                ("async", None) => (none, MountFlag::MS_SYNCHRONOUS),
+                ("noatime", None) => (MountFlag::MS_NOATIME, MountFlag::MS_STRICTATIME),
+                ("atime", None) => (none, MountFlag::MS_NOATIME),
+                ("nodiratime", None) => (MountFlag::MS_NODIRATIME, none),
+                ("diratime", None) => (none, MountFlag::MS_NODIRATIME),
+                ("relatime", None) => (MountFlag::MS_RELATIME, MountFlag::MS_STRICTATIME),
+                ("norelatime", None) => (none, MountFlag::MS_RELATIME),
+                ("strictatime", None) => (MountFlag::MS_STRICTATIME, atime),
+                _ => {
+                    opts.options
+                        .push((key.to_string(), value.map(|value| value.to_string())));
+                    continue;
+                }
+            };
+            opts.flags.remove(clear);
+            opts.flags.insert(set);
+        }
+        Ok(opts)
+    }
+}
+
+/* parse a size such as `16m` with an optional k, m or g suffix, see `memparse` in linux */
+pub fn memparse(s: &str) -> Result<usize> {
+    let (num, shift) = match s.chars().last() {
+        Some('k') | Some('K') => (&s[..s.len() - 1], 10),
+        Some('m') | Some('M') => (&s[..s.len() - 1], 20),
+        Some('g') | Some('G') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let num = num.parse::<usize>().map_err(|_| Error::new(EINVAL))?;
+    num.checked_mul(1 << shift)
+        .ok_or_else(|| Error::new(EINVAL))
 }
 
 #[derive(new)]
@@ -375,55 +2219,109 @@ impl fmt::Display for Dentry {
 
 pub trait INode: Sync + Send {
     fn get_ino(&self) -> usize;
-    fn get_metadata(&self) -> INodeMetaData;
+    fn get_metadata(&self) -> Result<INodeMetaData>;
     fn set_metadata(&self, metadata: &INodeMetaData);
     fn get_fs(&self) -> FSRef;
     fn get_dentries(&self) -> Vec<DentryRef>;
+    /* the file operations for a new open file of this inode, like `inode->i_fop` */
+    fn get_fop(&self) -> FileOperationsRef;
 
     // https://elixir.bootlin.com/linux/latest/source/include/linux/fs.h#L1970
     // inode_operations
     fn lookup(&self, dentry: &DentryRef, name: &str) -> Result<DentryRef>;
     //     const char * (*get_link) (struct dentry *, struct inode *, struct delayed_call *);
+    fn permission(&self, cred: &Credentials, mask: AccessMask) -> Result<()> {
+        generic_permission(&self.get_metadata()?, cred, mask)
+    }
     //     int (*permission) (struct inode *, int);
     //     struct posix_acl * (*get_acl)(struct inode *, int);
+    fn readlink(&self, dentry: &DentryRef) -> Result<String>;
     //     int (*readlink) (struct dentry *, char __user *,int);
-    fn create(&self, dentry: &DentryRef, name: &str) -> Result<DentryRef>;
+    /* `attr` holds the initial mode and owner of the new inode */
+    fn create(&self, dentry: &DentryRef, name: &str, attr: &INodeAttr) -> Result<DentryRef>;
     //     int (*create) (struct inode *,struct dentry *, umode_t, bool);
+    fn link(&self, dentry: &DentryRef, name: &str, target: &DentryRef) -> Result<DentryRef>;
     //     int (*link) (struct dentry *,struct inode *,struct dentry *);
     fn unlink(&self, dentry: &DentryRef, name: &str) -> Result<()>;
     //     int (*unlink) (struct inode *,struct dentry *);
+    fn symlink(
+        &self,
+        dentry: &DentryRef,
+        name: &str,
+        target: &str,
+        attr: &INodeAttr,
+    ) -> Result<DentryRef>;
     //     int (*symlink) (struct inode *,struct dentry *,const char *);
-    fn mkdir(&self, dentry: &DentryRef, name: &str) -> Result<DentryRef>;
+    fn mkdir(&self, dentry: &DentryRef, name: &str, attr: &INodeAttr) -> Result<DentryRef>;
     //     int (*mkdir) (struct inode *,struct dentry *,umode_t);
     // fn rmdir(&self, dentry: &DentryRef, name: &str, target: &DentryRef) -> Result<()>;
     //     int (*rmdir) (struct inode *,struct dentry *);
     //     int (*mknod) (struct inode *,struct dentry *,umode_t,dev_t);
-    // fn rename(&self, dentry: &DentryRef, name: &str, new_name: &str) -> Result<()>;
+    fn rename(
+        &self,
+        dentry: &DentryRef,
+        name: &str,
+        new_dentry: &DentryRef,
+        new_name: &str,
+        flags: RenameFlag,
+    ) -> Result<()>;
     //     int (*rename) (struct inode *, struct dentry *,
     //             struct inode *, struct dentry *, unsigned int);
     fn getattr(&self, dentry: &DentryRef, stat: &mut Stat) -> Result<()>;
+    fn setattr(&self, dentry: &DentryRef, attr: &INodeAttr) -> Result<()>;
     //     int (*setattr) (struct dentry *, struct iattr *);
     //     int (*getattr) (const struct path *, struct kstat *, u32, unsigned int);
     //     ssize_t (*listxattr) (struct dentry *, char *, size_t);
     //     int (*fiemap)(struct inode *, struct fiemap_extent_info *, u64 start,
     //               u64 len);
+    fn update_time(&self, time: usize, flags: TimeFlag) -> Result<()>;
     //     int (*update_time)(struct inode *, struct timespec64 *, int);
     //     int (*atomic_open)(struct inode *, struct dentry *,
     //                struct file *, unsigned open_flag,
     //                umode_t create_mode);
+    /* create a regular file without a name, its nlink stays 0 until it is linked */
+    fn tmpfile(&self, dentry: &DentryRef, attr: &INodeAttr) -> Result<DentryRef>;
     //     int (*tmpfile) (struct inode *, struct dentry *, umode_t);
     //     int (*set_acl)(struct inode *, struct posix_acl *, int);
 
+    /* the entries of a directory, used by the vfs itself, e.g. to check that it is empty */
+    fn readdir_inodes(&self, dentry: &DentryRef) -> Result<BTreeMap<String, usize>>;
+}
+
+/* operations on an open file, they are taken from `INode::get_fop` when the
+ * file is opened and live in `File::fop`, so devices, pipes and pseudo files
+ * can have their own i/o behavior */
+pub trait FileOperations: Sync + Send {
     // https://elixir.bootlin.com/linux/latest/source/include/linux/fs.h#L1923
     // struct file_operations
+    /* return the new offset, which is stored to `File::pos` by the vfs */
+    fn llseek(&self, file: &FileRef, offset: isize, whence: Whence) -> Result<usize> {
+        generic_file_llseek(file, offset, whence)
+    }
     //     loff_t (*llseek) (struct file *, loff_t, int);
-    fn read(&self, file: &FileRef, buf: &mut [u8]) -> Result<usize>;
+    fn read(&self, _file: &FileRef, _buf: &mut [u8]) -> Result<usize> {
+        Err(Error::new(EINVAL))
+    }
     //     ssize_t (*read) (struct file *, char __user *, size_t, loff_t *);
-    fn write(&self, file: &FileRef, buf: &[u8]) -> Result<usize>;
+    fn write(&self, _file: &FileRef, _buf: &[u8]) -> Result<usize> {
+        Err(Error::new(EINVAL))
+    }
     //     ssize_t (*write) (struct file *, const char __user *, size_t, loff_t *);
+    /* read and write at an explicit offset, `File::pos` is neither used nor
+     * changed, and `file` must not be locked since the caller may hold it */
+    fn read_at(&self, _file: &FileRef, _buf: &mut [u8], _offset: usize) -> Result<usize> {
+        Err(Error::new(EINVAL))
+    }
     //     ssize_t (*read_iter) (struct kiocb *, struct iov_iter *);
+    fn write_at(&self, _file: &FileRef, _buf: &[u8], _offset: usize) -> Result<usize> {
+        Err(Error::new(EINVAL))
+    }
     //     ssize_t (*write_iter) (struct kiocb *, struct iov_iter *);
     //     int (*iopoll)(struct kiocb *kiocb, bool spin);
+    /* `file.pos` starts from `DIR_DOT_ENTRIES`, `.` and `..` are not listed by the filesystem */
+    fn readdir(&self, _file: &FileRef, _dirs: &mut [Direntory]) -> Result<usize> {
+        Err(Error::new(ENOTDIR))
+    }
     //     int (*iterate) (struct file *, struct dir_context *);
     //     int (*iterate_shared) (struct file *, struct dir_context *);
     //     __poll_t (*poll) (struct file *, struct poll_table_struct *);
@@ -431,9 +2329,27 @@ pub trait INode: Sync + Send {
     //     long (*compat_ioctl) (struct file *, unsigned int, unsigned long);
     //     int (*mmap) (struct file *, struct vm_area_struct *);
     //     unsigned long mmap_supported_flags;
+    /* called once the `File` is set up, it may fill `File::private_data` or
+     * even replace `File::fop`, an error fails the open */
+    fn open(&self, _inode: &INodeRef, _file: &FileRef) -> Result<()> {
+        Ok(())
+    }
     //     int (*open) (struct inode *, struct file *);
+    /* called whenever a descriptor of the file is closed */
+    fn flush(&self, _file: &FileRef) -> Result<()> {
+        Ok(())
+    }
     //     int (*flush) (struct file *, fl_owner_t id);
+    /* called when the last reference to the file goes away */
+    fn release(&self, _inode: &INodeRef, _file: &FileRef) -> Result<()> {
+        Ok(())
+    }
     //     int (*release) (struct inode *, struct file *);
+    /* write the data and metadata of the file to its storage, with `datasync`
+     * only the metadata needed to read the data back, e.g. the size */
+    fn fsync(&self, _file: &FileRef, _datasync: bool) -> Result<()> {
+        Err(Error::new(EINVAL))
+    }
     //     int (*fsync) (struct file *, loff_t, loff_t, int datasync);
     //     int (*fasync) (int, struct file *, int);
     //     int (*lock) (struct file *, int, struct file_lock *);
@@ -460,8 +2376,6 @@ pub trait INode: Sync + Send {
     // int (*lseek) (struct inode *, struct file *, off_t, int);
     // int (*read) (struct inode *, struct file *, char *, int);
     // int (*write) (struct inode *, struct file *, const char *, int);
-    fn readdir_inodes(&self, dentry: &DentryRef) -> Result<BTreeMap<String, usize>>;
-    fn readdir(&self, file: &FileRef, dirs: &mut [Direntory]) -> Result<usize>;
     // int (*readdir) (struct inode *, struct file *, void *, filldir_t);
     // int (*select) (struct inode *, struct file *, int, select_table *);
     // int (*ioctl) (struct inode *, struct file *, unsigned int, unsigned long);
@@ -478,6 +2392,8 @@ pub trait INode: Sync + Send {
 pub struct INodeMetaData {
     pub mode: INodeType,
     #[new(default)]
+    pub perm: INodePerm,
+    #[new(default)]
     pub uid: usize,
     #[new(default)]
     pub gid: usize,
@@ -494,6 +2410,16 @@ pub struct INodeMetaData {
     // i_sb_list_next: *mut INode,
     #[new(default)]
     pub nlink: usize,
+    #[new(default)]
+    pub rdev: usize,
+    #[new(default)]
+    pub size: usize,
+    /* preferred I/O block size of the filesystem */
+    #[new(default)]
+    pub blksize: usize,
+    /* number of 512B blocks allocated */
+    #[new(default)]
+    pub blocks: usize,
     // i_private: *mut u8,
     #[new(default)]
     pub link: String,
@@ -503,19 +2429,137 @@ impl fmt::Debug for INodeMetaData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "ino: {} mode: {:?} nlink: {}",
-            self.ino, self.mode, self.nlink
+            "ino: {} mode: {:?} perm: {:o} nlink: {} size: {}",
+            self.ino,
+            self.mode,
+            self.perm.bits(),
+            self.nlink,
+            self.size
         )
     }
 }
 
+bitflags! {
+pub struct INodePerm:u32 {
+    const S_ISUID = 0o4000;     // set user id on execution
+    const S_ISGID = 0o2000;     // set group id on execution
+    const S_ISVTX = 0o1000;     // sticky bit
+    const S_IRUSR = 0o0400;
+    const S_IWUSR = 0o0200;
+    const S_IXUSR = 0o0100;
+    const S_IRGRP = 0o0040;
+    const S_IWGRP = 0o0020;
+    const S_IXGRP = 0o0010;
+    const S_IROTH = 0o0004;
+    const S_IWOTH = 0o0002;
+    const S_IXOTH = 0o0001;
+    const S_IRWXUGO = 0o0777;
+}
+}
+
+impl Default for INodePerm {
+    fn default() -> Self {
+        INodePerm::empty()
+    }
+}
+
+bitflags! {
+pub struct AccessMask:u32 {
+    const MAY_EXEC = 0b00000001;
+    const MAY_WRITE = 0b00000010;
+    const MAY_READ = 0b00000100;
+}
+}
+
+bitflags! {
+pub struct AttrMask:u32 {
+    const ATTR_MODE = 0b00000001;
+    const ATTR_UID = 0b00000010;
+    const ATTR_GID = 0b00000100;
+    const ATTR_SIZE = 0b00001000;
+    const ATTR_ATIME = 0b00010000;
+    const ATTR_MTIME = 0b00100000;
+    const ATTR_CTIME = 0b01000000;
+}
+}
+
+impl Default for AttrMask {
+    fn default() -> Self {
+        AttrMask::empty()
+    }
+}
+
+/* attributes to be changed by `setattr`, only the fields in `valid` are used,
+ * see `struct iattr` in linux */
+#[derive(Clone, Default, Debug)]
+pub struct INodeAttr {
+    pub valid: AttrMask,
+    pub perm: INodePerm,
+    pub uid: usize,
+    pub gid: usize,
+    pub size: usize,
+    pub atime: usize,
+    pub mtime: usize,
+    pub ctime: usize,
+}
+
+bitflags! {
+pub struct TimeFlag:u32 {
+    const S_ATIME = 0b00000001;
+    const S_MTIME = 0b00000010;
+    const S_CTIME = 0b00000100;
+}
+}
+
+/* a timestamp passed to `vfs_utimensat` */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UTime {
+    Now,  // UTIME_NOW
+    Omit, // UTIME_OMIT, leave the timestamp unchanged
+    At(usize),
+}
+
+impl UTime {
+    fn resolve(self, now: usize) -> Option<usize> {
+        match self {
+            UTime::Now => Some(now),
+            UTime::Omit => None,
+            UTime::At(time) => Some(time),
+        }
+    }
+}
+
+/* the origin of the offset passed to `vfs_lseek` */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Whence {
+    Set,  // SEEK_SET
+    Cur,  // SEEK_CUR
+    End,  // SEEK_END
+    Data, // SEEK_DATA, the next data at or after the offset
+    Hole, // SEEK_HOLE, the next hole at or after the offset
+}
+
+/* the source of the current time, registered by the kernel */
+pub trait Clock: Send + Sync {
+    fn now(&self) -> usize;
+}
+
 #[derive(new)]
 pub struct File {
     pub path: String,
     pub pos: usize,
     pub ref_count: usize,
+    pub mnt: MountRef,
+    pub dentry: DentryRef,
     pub inode: INodeRef,
+    pub fop: FileOperationsRef,
     pub mode: FileMode,
+    /* opaque state of the file operations, like `file->private_data` */
+    #[new(default)]
+    pub private_data: Option<Box<dyn Any + Send + Sync>>,
+    /* an unnamed file from `vfs_tmpfile` which may still be linked */
+    #[new(default)]
+    pub linkable: bool,
 }
 
 impl fmt::Display for File {